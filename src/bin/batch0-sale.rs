@@ -0,0 +1,278 @@
+//admin CLI for batch0 sales, built with `--features cli`
+#![allow(clippy::needless_return)]
+
+use std::{error::Error, fs, path::PathBuf};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+
+use batch0_sale_solana::{
+    instruction::{self, Batch0SaleInstruction},
+    pda::find_sale_state_address,
+    state::Batch0SaleProgramData,
+};
+
+#[derive(Parser)]
+#[command(name = "batch0-sale", about = "Administer batch0 token sales")]
+struct Cli {
+    #[arg(long)]
+    program_id: Pubkey,
+
+    /// Signs as the sale authority (the inventory for `init`) and pays fees
+    #[arg(long, default_value = "~/.config/solana/id.json")]
+    keypair: String,
+
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Print the signed transaction as base64 instead of sending it
+    #[arg(long)]
+    offline: bool,
+
+    /// Blockhash to sign with, required in offline mode by every command but `show`
+    #[arg(long, requires = "offline")]
+    blockhash: Option<Hash>,
+
+    /// Raw sale account data, read instead of fetching the account
+    #[arg(long)]
+    sale_data: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the sale state for `mint` and hand the shelf to the sale
+    Init {
+        #[arg(long)]
+        shelf_keypair: String,
+        #[arg(long)]
+        till: Pubkey,
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        price: u64,
+        #[arg(long, default_value_t = 0)]
+        start_ts: i64,
+        #[arg(long, default_value_t = 0)]
+        end_ts: i64,
+        #[arg(long, default_value_t = 0)]
+        sale_id: u64,
+    },
+    UpdatePrice {
+        sale: Pubkey,
+        price: u64,
+    },
+    Pause {
+        sale: Pubkey,
+    },
+    Resume {
+        sale: Pubkey,
+    },
+    /// Sweep the shelf into the inventory's token account and close the sale
+    End {
+        sale: Pubkey,
+        #[arg(long)]
+        inventory_token_account: Pubkey,
+    },
+    /// Print the decoded sale state
+    Show {
+        sale: Pubkey,
+    },
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let rpc_client = RpcClient::new_with_commitment(cli.url.clone(), CommitmentConfig::confirmed());
+
+    if let Command::Show { sale } = &cli.command {
+        let sale_data = load_sale(&cli, &rpc_client, sale)?;
+        print_sale(sale, &sale_data);
+        return Ok(());
+    }
+
+    //everything past `show` signs a transaction, which offline needs a real blockhash for
+    if cli.offline && cli.blockhash.is_none() {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--blockhash is required to sign a transaction with --offline",
+            )
+            .exit();
+    }
+
+    let payer = read_keypair(&cli.keypair)?;
+    let mut signers = vec![&payer];
+    let shelf_keypair;
+
+    let instruction = match &cli.command {
+        Command::Init {
+            shelf_keypair: shelf_keypair_path,
+            till,
+            mint,
+            price,
+            start_ts,
+            end_ts,
+            sale_id,
+        } => {
+            shelf_keypair = read_keypair(shelf_keypair_path)?;
+            signers.push(&shelf_keypair);
+
+            let (sale, _) =
+                find_sale_state_address(mint, &payer.pubkey(), *sale_id, &cli.program_id);
+            println!("Sale state: {}", sale);
+            instruction::init_sale(
+                &cli.program_id,
+                &payer.pubkey(),
+                till,
+                &shelf_keypair.pubkey(),
+                mint,
+                *price,
+                *start_ts,
+                *end_ts,
+                *sale_id,
+            )
+        }
+        Command::UpdatePrice { sale, price } => {
+            instruction::update_price(&cli.program_id, &payer.pubkey(), sale, *price)
+        }
+        Command::Pause { sale } => instruction::update_sale(
+            &cli.program_id,
+            &payer.pubkey(),
+            sale,
+            Batch0SaleInstruction::PauseSale {},
+        ),
+        Command::Resume { sale } => instruction::update_sale(
+            &cli.program_id,
+            &payer.pubkey(),
+            sale,
+            Batch0SaleInstruction::ResumeSale {},
+        ),
+        Command::End {
+            sale,
+            inventory_token_account,
+        } => {
+            let sale_data = load_sale(&cli, &rpc_client, sale)?;
+            instruction::end_token_sale(
+                &cli.program_id,
+                &payer.pubkey(),
+                sale,
+                &sale_data,
+                inventory_token_account,
+                &[],
+            )
+        }
+        Command::Show { .. } => unreachable!(),
+    };
+
+    return submit(&cli, &rpc_client, instruction, &payer, &signers);
+}
+
+fn submit(
+    cli: &Cli,
+    rpc_client: &RpcClient,
+    instruction: Instruction,
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), Box<dyn Error>> {
+    //--blockhash is only accepted together with --offline, and main requires it there
+    let blockhash = match cli.blockhash {
+        Some(blockhash) => blockhash,
+        None => rpc_client.get_latest_blockhash()?,
+    };
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        signers,
+        blockhash,
+    );
+
+    if cli.offline {
+        println!("{}", STANDARD.encode(bincode::serialize(&transaction)?));
+        return Ok(());
+    }
+
+    let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
+    println!("Signature: {}", signature);
+
+    return Ok(());
+}
+
+fn read_keypair(path: &str) -> Result<Keypair, Box<dyn Error>> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME")?).join(rest),
+        None => PathBuf::from(path),
+    };
+
+    return read_keypair_file(&path)
+        .map_err(|err| format!("reading keypair {}: {}", path.display(), err).into());
+}
+
+fn load_sale(
+    cli: &Cli,
+    rpc_client: &RpcClient,
+    sale: &Pubkey,
+) -> Result<Batch0SaleProgramData, Box<dyn Error>> {
+    let data = match &cli.sale_data {
+        Some(path) => fs::read(path)?,
+        None if cli.offline => return Err("--sale-data is required in offline mode".into()),
+        None => rpc_client.get_account_data(sale)?,
+    };
+
+    return Ok(Batch0SaleProgramData::unpack_any_version(&data)?);
+}
+
+fn print_sale(sale: &Pubkey, data: &Batch0SaleProgramData) {
+    println!("Sale:                  {}", sale);
+    println!("Version:               {}", data.version);
+    println!("Status:                {:?}", data.status);
+    println!("Sale id:               {}", data.sale_id);
+    println!("Price per token:       {}", data.current_price_per_token);
+    println!("Mint:                  {} ({} decimals)", data.mint_pubkey, data.mint_decimals);
+    println!("Inventory:             {}", data.inventory_pubkey);
+    println!("Shelf:                 {}", data.shelf_pubkey);
+    println!("Till:                  {}", data.till_pubkey);
+    println!("Authority:             {}", data.authority);
+    if data.pending_authority != Pubkey::default() {
+        println!("Pending authority:     {}", data.pending_authority);
+    }
+    if data.payment_mint_pubkey != Pubkey::default() {
+        println!("Payment mint:          {}", data.payment_mint_pubkey);
+    }
+    println!("Window:                {} - {}", data.start_ts, data.end_ts);
+    println!("Max tokens per wallet: {}", data.max_tokens_per_wallet);
+    println!("Presale:               {}", data.is_presale());
+    println!("Receipts enabled:      {}", data.receipts_enabled);
+    println!("Transfer fee mode:     {:?}", data.transfer_fee_mode);
+    println!("Tokens sold:           {}", data.total_tokens_sold);
+    println!("Bonus issued:          {}", data.total_bonus_issued);
+    println!("Collected:             {}", data.total_collected);
+    println!("Purchases:             {}", data.total_purchases);
+
+    for (tier_id, tier) in data.tiers.iter().enumerate() {
+        if !tier.is_initialized {
+            continue;
+        }
+        let name_len = tier.name.iter().position(|byte| *byte == 0).unwrap_or(tier.name.len());
+        println!(
+            "Tier {}: {:<16} sold {} bonus {} purchases {}{}",
+            tier_id,
+            String::from_utf8_lossy(&tier.name[..name_len]),
+            tier.sold_tokens,
+            tier.bonus_tokens,
+            tier.purchase_count,
+            if tier.is_enabled { "" } else { " (disabled)" }
+        );
+    }
+}
@@ -6,6 +6,82 @@ use solana_program::program_error::ProgramError;
 pub enum CustomError {
     #[error("invalid instruction")]
     InvalidInstruction,
+    #[error("package tier does not exist")]
+    InvalidTier,
+    #[error("package tier already exists")]
+    TierAlreadyExists,
+    #[error("package tier is disabled")]
+    TierDisabled,
+    #[error("purchase amount must be greater than zero")]
+    InvalidPurchaseAmount,
+    #[error("price changed beyond what the buyer signed for")]
+    PriceChanged,
+    #[error("bonus breakpoint exceeds 100%")]
+    InvalidBonusSchedule,
+    #[error("payment mint does not match the sale")]
+    InvalidPaymentMint,
+    #[error("sale has not started")]
+    SaleNotStarted,
+    #[error("sale has ended")]
+    SaleEnded,
+    #[error("sale window ends before it starts")]
+    InvalidSaleWindow,
+    #[error("purchase exceeds the per-wallet cap")]
+    WalletCapExceeded,
+    #[error("buyer record does not belong to this sale and buyer")]
+    InvalidBuyerRecord,
+    #[error("presale purchases require an allowlist proof")]
+    PresaleProofRequired,
+    #[error("allowlist proof does not match the presale root")]
+    InvalidMerkleProof,
+    #[error("purchase exceeds the buyer's presale allocation")]
+    AllocationExceeded,
+    #[error("account is not the sale authority")]
+    WrongAuthority,
+    #[error("sale authority must sign")]
+    MissingAuthoritySignature,
+    #[error("no authority transfer is pending")]
+    NoPendingAuthority,
+    #[error("account must be a signer")]
+    AccountNotSigner,
+    #[error("account must be writable")]
+    AccountNotWritable,
+    #[error("account has the wrong owner")]
+    IncorrectAccountOwner,
+    #[error("token program must be Token-2022")]
+    IncorrectTokenProgram,
+    #[error("system program account is not the system program")]
+    IncorrectSystemProgram,
+    #[error("account is not a valid mint")]
+    InvalidMintAccount,
+    #[error("account is not a valid token account")]
+    InvalidTokenAccount,
+    #[error("token account holds a different mint")]
+    MintMismatch,
+    #[error("till account does not match the sale")]
+    InvalidTillAccount,
+    #[error("shelf account does not match the sale")]
+    InvalidShelfAccount,
+    #[error("inventory account does not match the sale")]
+    InvalidInventoryAccount,
+    #[error("sale authority account is not the sale's PDA")]
+    InvalidSaleAuthority,
+    #[error("sale is paused")]
+    SalePaused,
+    #[error("sale state uses an older layout and must be migrated")]
+    StateMigrationRequired,
+    #[error("sale state is already on the current layout")]
+    StateAlreadyCurrent,
+    #[error("sale state account is not the PDA for this mint and sale id")]
+    InvalidSaleStateAccount,
+    #[error("receipt account is not the PDA for this purchase")]
+    InvalidReceiptAccount,
+    #[error("associated token program account is not the associated token program")]
+    IncorrectAssociatedTokenProgram,
+    #[error("buyer token account is not the gift recipient's associated token account")]
+    InvalidRecipientTokenAccount,
+    #[error("payment token program must be SPL Token or Token-2022")]
+    IncorrectPaymentTokenProgram,
 }
 
 impl From<CustomError> for ProgramError {
@@ -0,0 +1,594 @@
+use solana_program::{log::sol_log_data, program_error::ProgramError, pubkey::Pubkey};
+use std::convert::TryInto;
+
+use crate::state::{BonusBreakpoint, SaleStatus, TransferFeeMode, MAX_BONUS_BREAKPOINTS};
+
+//events are logged through sol_log_data as a single field: a tag byte
+//followed by the variant's fields, little-endian and in declaration order.
+//Tags and field order are append-only, and fields added to an existing
+//variant are read with `EventReader::appended`, so logs written before a
+//field existed decode it as that field's default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Batch0SaleEvent {
+    SaleInitialized {
+        sale: Pubkey,
+        mint: Pubkey,
+        authority: Pubkey,
+        sale_id: u64,
+        price_per_token: u64,
+        start_ts: i64,
+        end_ts: i64,
+    },
+    PriceUpdated {
+        sale: Pubkey,
+        old_price_per_token: u64,
+        new_price_per_token: u64,
+    },
+    Purchased {
+        sale: Pubkey,
+        buyer: Pubkey,
+        tier_id: Option<u8>,
+        sold_tokens: u64,
+        bonus_tokens: u64,
+        amount_paid: u64,
+        price_per_token: u64,
+        purchase_index: u64,
+        transfer_fee: u64,
+        recipient: Pubkey,
+    },
+    SaleEnded {
+        sale: Pubkey,
+        unsold_tokens: u64,
+    },
+    TierUpdated {
+        sale: Pubkey,
+        tier_id: u8,
+    },
+    StatusChanged {
+        sale: Pubkey,
+        status: SaleStatus,
+    },
+    AuthorityProposed {
+        sale: Pubkey,
+        authority: Pubkey,
+        pending_authority: Pubkey,
+    },
+    AuthorityChanged {
+        sale: Pubkey,
+        old_authority: Pubkey,
+        new_authority: Pubkey,
+    },
+    SaleWindowChanged {
+        sale: Pubkey,
+        start_ts: i64,
+        end_ts: i64,
+    },
+    //payment_mint is the default pubkey when the sale is back on lamports
+    PaymentMintChanged {
+        sale: Pubkey,
+        payment_mint: Pubkey,
+        till: Pubkey,
+    },
+    WalletCapChanged {
+        sale: Pubkey,
+        max_tokens_per_wallet: u64,
+    },
+    //an all-zero root ends the presale
+    MerkleRootChanged {
+        sale: Pubkey,
+        merkle_root: [u8; 32],
+    },
+    BonusScheduleChanged {
+        sale: Pubkey,
+        bonus_schedule: [BonusBreakpoint; MAX_BONUS_BREAKPOINTS],
+    },
+    ReceiptsEnabledChanged {
+        sale: Pubkey,
+        receipts_enabled: bool,
+    },
+    TransferFeeModeChanged {
+        sale: Pubkey,
+        transfer_fee_mode: TransferFeeMode,
+    },
+    StateMigrated {
+        sale: Pubkey,
+        old_version: u8,
+        new_version: u8,
+    },
+}
+
+impl Batch0SaleEvent {
+    pub fn emit(&self) {
+        sol_log_data(&[&self.pack()]);
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Self::SaleInitialized {
+                sale,
+                mint,
+                authority,
+                sale_id,
+                price_per_token,
+                start_ts,
+                end_ts,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(mint.as_ref());
+                buf.extend_from_slice(authority.as_ref());
+                buf.extend_from_slice(&sale_id.to_le_bytes());
+                buf.extend_from_slice(&price_per_token.to_le_bytes());
+                buf.extend_from_slice(&start_ts.to_le_bytes());
+                buf.extend_from_slice(&end_ts.to_le_bytes());
+            }
+            Self::PriceUpdated {
+                sale,
+                old_price_per_token,
+                new_price_per_token,
+            } => {
+                buf.push(1);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(&old_price_per_token.to_le_bytes());
+                buf.extend_from_slice(&new_price_per_token.to_le_bytes());
+            }
+            Self::Purchased {
+                sale,
+                buyer,
+                tier_id,
+                sold_tokens,
+                bonus_tokens,
+                amount_paid,
+                price_per_token,
+                purchase_index,
+                transfer_fee,
+                recipient,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(buyer.as_ref());
+                //presence byte, then the tier id (0 for an arbitrary-quantity buy)
+                buf.push(tier_id.is_some() as u8);
+                buf.push(tier_id.unwrap_or(0));
+                buf.extend_from_slice(&sold_tokens.to_le_bytes());
+                buf.extend_from_slice(&bonus_tokens.to_le_bytes());
+                buf.extend_from_slice(&amount_paid.to_le_bytes());
+                buf.extend_from_slice(&price_per_token.to_le_bytes());
+                buf.extend_from_slice(&purchase_index.to_le_bytes());
+                buf.extend_from_slice(&transfer_fee.to_le_bytes());
+                buf.extend_from_slice(recipient.as_ref());
+            }
+            Self::SaleEnded {
+                sale,
+                unsold_tokens,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(&unsold_tokens.to_le_bytes());
+            }
+            Self::TierUpdated { sale, tier_id } => {
+                buf.push(4);
+                buf.extend_from_slice(sale.as_ref());
+                buf.push(*tier_id);
+            }
+            Self::StatusChanged { sale, status } => {
+                buf.push(5);
+                buf.extend_from_slice(sale.as_ref());
+                buf.push(*status as u8);
+            }
+            Self::AuthorityProposed {
+                sale,
+                authority,
+                pending_authority,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(authority.as_ref());
+                buf.extend_from_slice(pending_authority.as_ref());
+            }
+            Self::AuthorityChanged {
+                sale,
+                old_authority,
+                new_authority,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(old_authority.as_ref());
+                buf.extend_from_slice(new_authority.as_ref());
+            }
+            Self::SaleWindowChanged {
+                sale,
+                start_ts,
+                end_ts,
+            } => {
+                buf.push(8);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(&start_ts.to_le_bytes());
+                buf.extend_from_slice(&end_ts.to_le_bytes());
+            }
+            Self::PaymentMintChanged {
+                sale,
+                payment_mint,
+                till,
+            } => {
+                buf.push(9);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(payment_mint.as_ref());
+                buf.extend_from_slice(till.as_ref());
+            }
+            Self::WalletCapChanged {
+                sale,
+                max_tokens_per_wallet,
+            } => {
+                buf.push(10);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(&max_tokens_per_wallet.to_le_bytes());
+            }
+            Self::MerkleRootChanged { sale, merkle_root } => {
+                buf.push(11);
+                buf.extend_from_slice(sale.as_ref());
+                buf.extend_from_slice(merkle_root);
+            }
+            Self::BonusScheduleChanged {
+                sale,
+                bonus_schedule,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(sale.as_ref());
+                for breakpoint in bonus_schedule {
+                    buf.extend_from_slice(&breakpoint.min_tokens.to_le_bytes());
+                    buf.extend_from_slice(&breakpoint.bonus_bps.to_le_bytes());
+                }
+            }
+            Self::ReceiptsEnabledChanged {
+                sale,
+                receipts_enabled,
+            } => {
+                buf.push(13);
+                buf.extend_from_slice(sale.as_ref());
+                buf.push(*receipts_enabled as u8);
+            }
+            Self::TransferFeeModeChanged {
+                sale,
+                transfer_fee_mode,
+            } => {
+                buf.push(14);
+                buf.extend_from_slice(sale.as_ref());
+                buf.push(*transfer_fee_mode as u8);
+            }
+            Self::StateMigrated {
+                sale,
+                old_version,
+                new_version,
+            } => {
+                buf.push(15);
+                buf.extend_from_slice(sale.as_ref());
+                buf.push(*old_version);
+                buf.push(*new_version);
+            }
+        }
+        return buf;
+    }
+
+    //decodes one `Program data:` field, after the indexer has base64-decoded it
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input.split_first().ok_or(ProgramError::InvalidArgument)?;
+        let mut reader = EventReader { input: rest };
+
+        let event = match tag {
+            0 => Self::SaleInitialized {
+                sale: reader.pubkey()?,
+                mint: reader.pubkey()?,
+                authority: reader.pubkey()?,
+                sale_id: reader.u64()?,
+                price_per_token: reader.u64()?,
+                start_ts: reader.u64()? as i64,
+                end_ts: reader.u64()? as i64,
+            },
+            1 => Self::PriceUpdated {
+                sale: reader.pubkey()?,
+                old_price_per_token: reader.u64()?,
+                new_price_per_token: reader.u64()?,
+            },
+            2 => {
+                let sale = reader.pubkey()?;
+                let buyer = reader.pubkey()?;
+                Self::Purchased {
+                    sale,
+                    buyer,
+                    tier_id: match (reader.u8()?, reader.u8()?) {
+                        (0, _) => None,
+                        (1, tier_id) => Some(tier_id),
+                        _ => return Err(ProgramError::InvalidArgument),
+                    },
+                    sold_tokens: reader.u64()?,
+                    bonus_tokens: reader.u64()?,
+                    amount_paid: reader.u64()?,
+                    price_per_token: reader.u64()?,
+                    purchase_index: reader.u64()?,
+                    //no fee was tracked before transfer_fee, and purchases
+                    //went to the buyer before recipient
+                    transfer_fee: reader.appended(EventReader::u64, 0)?,
+                    recipient: reader.appended(EventReader::pubkey, buyer)?,
+                }
+            }
+            3 => Self::SaleEnded {
+                sale: reader.pubkey()?,
+                unsold_tokens: reader.u64()?,
+            },
+            4 => Self::TierUpdated {
+                sale: reader.pubkey()?,
+                tier_id: reader.u8()?,
+            },
+            5 => Self::StatusChanged {
+                sale: reader.pubkey()?,
+                status: SaleStatus::from_u8(reader.u8()?).ok_or(ProgramError::InvalidArgument)?,
+            },
+            6 => Self::AuthorityProposed {
+                sale: reader.pubkey()?,
+                authority: reader.pubkey()?,
+                pending_authority: reader.pubkey()?,
+            },
+            7 => Self::AuthorityChanged {
+                sale: reader.pubkey()?,
+                old_authority: reader.pubkey()?,
+                new_authority: reader.pubkey()?,
+            },
+            8 => Self::SaleWindowChanged {
+                sale: reader.pubkey()?,
+                start_ts: reader.u64()? as i64,
+                end_ts: reader.u64()? as i64,
+            },
+            9 => Self::PaymentMintChanged {
+                sale: reader.pubkey()?,
+                payment_mint: reader.pubkey()?,
+                till: reader.pubkey()?,
+            },
+            10 => Self::WalletCapChanged {
+                sale: reader.pubkey()?,
+                max_tokens_per_wallet: reader.u64()?,
+            },
+            11 => Self::MerkleRootChanged {
+                sale: reader.pubkey()?,
+                merkle_root: reader.take()?,
+            },
+            12 => {
+                let sale = reader.pubkey()?;
+                let mut bonus_schedule = [BonusBreakpoint::default(); MAX_BONUS_BREAKPOINTS];
+                for breakpoint in bonus_schedule.iter_mut() {
+                    breakpoint.min_tokens = reader.u64()?;
+                    breakpoint.bonus_bps = u16::from_le_bytes(reader.take()?);
+                }
+                Self::BonusScheduleChanged {
+                    sale,
+                    bonus_schedule,
+                }
+            }
+            13 => Self::ReceiptsEnabledChanged {
+                sale: reader.pubkey()?,
+                receipts_enabled: match reader.u8()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(ProgramError::InvalidArgument),
+                },
+            },
+            14 => Self::TransferFeeModeChanged {
+                sale: reader.pubkey()?,
+                transfer_fee_mode: TransferFeeMode::from_u8(reader.u8()?)
+                    .ok_or(ProgramError::InvalidArgument)?,
+            },
+            15 => Self::StateMigrated {
+                sale: reader.pubkey()?,
+                old_version: reader.u8()?,
+                new_version: reader.u8()?,
+            },
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+
+        return Ok(event);
+    }
+}
+
+struct EventReader<'a> {
+    input: &'a [u8],
+}
+
+impl EventReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ProgramError> {
+        let bytes = self
+            .input
+            .get(..N)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(ProgramError::InvalidArgument)?;
+        self.input = &self.input[N..];
+
+        return Ok(bytes);
+    }
+
+    fn u8(&mut self) -> Result<u8, ProgramError> {
+        return Ok(self.take::<1>()?[0]);
+    }
+
+    fn u64(&mut self) -> Result<u64, ProgramError> {
+        return Ok(u64::from_le_bytes(self.take()?));
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, ProgramError> {
+        return Ok(Pubkey::new_from_array(self.take()?));
+    }
+
+    //a field appended to a variant after it first shipped: `default` when the
+    //log ends before it, but a partially written field is still an error
+    fn appended<T>(
+        &mut self,
+        read: fn(&mut Self) -> Result<T, ProgramError>,
+        default: T,
+    ) -> Result<T, ProgramError> {
+        if self.input.is_empty() {
+            return Ok(default);
+        }
+
+        return read(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn purchased(tier_id: Option<u8>) -> Batch0SaleEvent {
+        return Batch0SaleEvent::Purchased {
+            sale: Pubkey::new_from_array([1; 32]),
+            buyer: Pubkey::new_from_array([2; 32]),
+            tier_id,
+            sold_tokens: 1_500_000,
+            bonus_tokens: 375_000,
+            amount_paid: 3_000_000,
+            price_per_token: 2,
+            purchase_index: 4,
+            transfer_fee: 15_000,
+            recipient: Pubkey::new_from_array([3; 32]),
+        };
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        let sale = Pubkey::new_from_array([1; 32]);
+        let events = [
+            Batch0SaleEvent::SaleInitialized {
+                sale,
+                mint: Pubkey::new_from_array([2; 32]),
+                authority: Pubkey::new_from_array([3; 32]),
+                sale_id: 7,
+                price_per_token: 2,
+                start_ts: -1,
+                end_ts: 1_700_000_000,
+            },
+            Batch0SaleEvent::PriceUpdated {
+                sale,
+                old_price_per_token: 2,
+                new_price_per_token: 3,
+            },
+            purchased(None),
+            purchased(Some(0)),
+            purchased(Some(5)),
+            Batch0SaleEvent::SaleEnded {
+                sale,
+                unsold_tokens: 42,
+            },
+            Batch0SaleEvent::TierUpdated { sale, tier_id: 3 },
+            Batch0SaleEvent::StatusChanged {
+                sale,
+                status: SaleStatus::Paused,
+            },
+            Batch0SaleEvent::AuthorityProposed {
+                sale,
+                authority: Pubkey::new_from_array([2; 32]),
+                pending_authority: Pubkey::new_from_array([3; 32]),
+            },
+            Batch0SaleEvent::AuthorityChanged {
+                sale,
+                old_authority: Pubkey::new_from_array([2; 32]),
+                new_authority: Pubkey::new_from_array([3; 32]),
+            },
+            Batch0SaleEvent::SaleWindowChanged {
+                sale,
+                start_ts: -1,
+                end_ts: 1_700_000_000,
+            },
+            Batch0SaleEvent::PaymentMintChanged {
+                sale,
+                payment_mint: Pubkey::new_from_array([2; 32]),
+                till: Pubkey::new_from_array([3; 32]),
+            },
+            Batch0SaleEvent::WalletCapChanged {
+                sale,
+                max_tokens_per_wallet: 10_000,
+            },
+            Batch0SaleEvent::MerkleRootChanged {
+                sale,
+                merkle_root: [9; 32],
+            },
+            Batch0SaleEvent::BonusScheduleChanged {
+                sale,
+                bonus_schedule: BonusBreakpoint::default_schedule(),
+            },
+            Batch0SaleEvent::ReceiptsEnabledChanged {
+                sale,
+                receipts_enabled: true,
+            },
+            Batch0SaleEvent::TransferFeeModeChanged {
+                sale,
+                transfer_fee_mode: TransferFeeMode::ChargeBuyer,
+            },
+            Batch0SaleEvent::StateMigrated {
+                sale,
+                old_version: 1,
+                new_version: 2,
+            },
+        ];
+
+        for event in events {
+            assert_eq!(Batch0SaleEvent::unpack(&event.pack()).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn purchased_keeps_tier_zero_apart_from_no_tier() {
+        let none = purchased(None).pack();
+        let zero = purchased(Some(0)).pack();
+
+        //tag, sale, buyer, then the presence byte and tier id
+        assert_eq!(none[65..67], [0, 0]);
+        assert_eq!(zero[65..67], [1, 0]);
+    }
+
+    #[test]
+    fn decodes_purchases_logged_before_appended_fields() {
+        let event = purchased(Some(2));
+        let packed = event.pack();
+
+        //before recipient, the tokens always went to the buyer
+        let Batch0SaleEvent::Purchased {
+            buyer,
+            transfer_fee,
+            recipient,
+            ..
+        } = Batch0SaleEvent::unpack(&packed[..packed.len() - 32]).unwrap()
+        else {
+            panic!("not a purchase");
+        };
+        assert_eq!(recipient, buyer);
+        assert_eq!(transfer_fee, 15_000);
+
+        //before transfer_fee, no fee was recorded
+        let Batch0SaleEvent::Purchased {
+            transfer_fee,
+            recipient,
+            purchase_index,
+            ..
+        } = Batch0SaleEvent::unpack(&packed[..packed.len() - 40]).unwrap()
+        else {
+            panic!("not a purchase");
+        };
+        assert_eq!((transfer_fee, purchase_index), (0, 4));
+        assert_eq!(recipient, buyer);
+
+        //fields from the original layout are still required
+        assert!(Batch0SaleEvent::unpack(&packed[..packed.len() - 48]).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_events() {
+        let mut bad_presence = purchased(Some(1)).pack();
+        bad_presence[65] = 2;
+        assert!(Batch0SaleEvent::unpack(&bad_presence).is_err());
+
+        let truncated = purchased(None).pack();
+        assert!(Batch0SaleEvent::unpack(&truncated[..truncated.len() - 1]).is_err());
+
+        assert!(Batch0SaleEvent::unpack(&[16]).is_err());
+        assert!(Batch0SaleEvent::unpack(&[]).is_err());
+    }
+}
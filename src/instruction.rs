@@ -1,18 +1,85 @@
-use solana_program::{msg, program_error::ProgramError};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
 use std::convert::TryInto;
 
 use crate::error::CustomError::InvalidInstruction;
+use crate::pda::{
+    find_buyer_record_address, find_legacy_sale_authority_address, find_receipt_address,
+    find_sale_authority_address, find_sale_state_address,
+};
+use crate::state::{
+    Batch0SaleProgramData, BonusBreakpoint, TransferFeeMode, MAX_BONUS_BREAKPOINTS, TIER_NAME_LEN,
+};
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum Batch0SaleInstruction {
-    InitSale { new_price_per_token: u64 },
+    InitSale {
+        new_price_per_token: u64,
+        start_ts: i64,
+        end_ts: i64,
+        sale_id: u64,
+    },
     UpdatePrice { new_price_per_token: u64 },
     EndTokenSale {},
-    BuyShot {},
-    BuyDouble {},
-    BuyFlask {},
-    BuyFifth {},
-    BuyCase {},
-    BuyBarrel {},
+    BuyPackage {
+        tier_id: u8,
+        max_total_lamports: u64,
+        expected_price: u64,
+        create_buyer_token_account: bool,
+        is_gift: bool,
+    },
+    AddTier {
+        tier_id: u8,
+        name: [u8; TIER_NAME_LEN],
+        sold_tokens: u64,
+        bonus_tokens: u64,
+    },
+    EditTier {
+        tier_id: u8,
+        name: [u8; TIER_NAME_LEN],
+        sold_tokens: u64,
+        bonus_tokens: u64,
+    },
+    EnableTier { tier_id: u8 },
+    DisableTier { tier_id: u8 },
+    RemoveTier { tier_id: u8 },
+    BuyTokens {
+        amount: u64,
+        max_total_lamports: u64,
+        expected_price: u64,
+        create_buyer_token_account: bool,
+        is_gift: bool,
+    },
+    SetBonusSchedule {
+        bonus_schedule: [BonusBreakpoint; MAX_BONUS_BREAKPOINTS],
+    },
+    //the price is given in the new currency's base units, so it switches
+    //together with the currency
+    SetPaymentMint { new_price_per_token: u64 },
+    SetSaleWindow { start_ts: i64, end_ts: i64 },
+    SetWalletCap { max_tokens_per_wallet: u64 },
+    SetMerkleRoot { merkle_root: [u8; 32] },
+    BuyPackageWithProof {
+        tier_id: u8,
+        max_total_lamports: u64,
+        expected_price: u64,
+        allocation: u64,
+        proof: Vec<[u8; 32]>,
+        create_buyer_token_account: bool,
+        is_gift: bool,
+    },
+    ProposeAuthority { new_authority: Pubkey },
+    AcceptAuthority {},
+    PauseSale {},
+    ResumeSale {},
+    MigrateState {},
+    SetReceiptsEnabled { receipts_enabled: bool },
+    SetTransferFeeMode { transfer_fee_mode: TransferFeeMode },
 }
 
 //function of enum
@@ -27,20 +94,268 @@ impl Batch0SaleInstruction {
         return match tag {
             0 => Ok(Self::InitSale {
                 new_price_per_token: Self::unpack_byte(rest, 0)?,
+                start_ts: Self::unpack_byte(rest, 1)? as i64,
+                end_ts: Self::unpack_byte(rest, 2)? as i64,
+                sale_id: Self::unpack_byte(rest, 3)?,
             }),
             1 => Ok(Self::UpdatePrice {
                 new_price_per_token: Self::unpack_byte(rest, 0)?,
             }),
             2 => Ok(Self::EndTokenSale {}),
-            3 => Ok(Self::BuyShot {}),
-            4 => Ok(Self::BuyDouble {}),
-            5 => Ok(Self::BuyFlask {}),
-            6 => Ok(Self::BuyFifth {}),
-            7 => Ok(Self::BuyCase {}),
-            8 => Ok(Self::BuyBarrel {}),
+            //3-8 were BuyShot..BuyBarrel; retired rather than reused so a stale
+            //client's package buy cannot decode as a different instruction
+            3..=8 => Err(InvalidInstruction.into()),
+            9 => Ok(Self::BuyTokens {
+                amount: Self::unpack_byte(rest, 0)?,
+                max_total_lamports: Self::unpack_byte(rest, 1)?,
+                expected_price: Self::unpack_byte(rest, 2)?,
+                create_buyer_token_account: Self::unpack_trailing_flag(rest, 24)?,
+                is_gift: Self::unpack_trailing_flag(rest, 25)?,
+            }),
+            10 => {
+                let mut bonus_schedule = [BonusBreakpoint::default(); MAX_BONUS_BREAKPOINTS];
+                for (index, breakpoint) in bonus_schedule.iter_mut().enumerate() {
+                    let offset = index * BonusBreakpoint::LEN;
+                    let breakpoint_bytes = rest
+                        .get(offset..offset + BonusBreakpoint::LEN)
+                        .ok_or(InvalidInstruction)?;
+                    *breakpoint = BonusBreakpoint::unpack_from_slice(breakpoint_bytes)?;
+                }
+                Ok(Self::SetBonusSchedule { bonus_schedule })
+            }
+            11 => Ok(Self::SetPaymentMint {
+                new_price_per_token: Self::unpack_u64(rest, 0)?,
+            }),
+            12 => Ok(Self::SetSaleWindow {
+                start_ts: Self::unpack_byte(rest, 0)? as i64,
+                end_ts: Self::unpack_byte(rest, 1)? as i64,
+            }),
+            13 => Ok(Self::SetWalletCap {
+                max_tokens_per_wallet: Self::unpack_byte(rest, 0)?,
+            }),
+            14 => Ok(Self::SetMerkleRoot {
+                merkle_root: Self::unpack_array(rest, 0)?,
+            }),
+            15 => {
+                let proof_len = Self::unpack_u8(rest, 25)? as usize;
+                let proof = (0..proof_len)
+                    .map(|index| Self::unpack_array(rest, 26 + index * 32))
+                    .collect::<Result<Vec<[u8; 32]>, ProgramError>>()?;
+                Ok(Self::BuyPackageWithProof {
+                    tier_id: Self::unpack_u8(rest, 0)?,
+                    max_total_lamports: Self::unpack_u64(rest, 1)?,
+                    expected_price: Self::unpack_u64(rest, 9)?,
+                    allocation: Self::unpack_u64(rest, 17)?,
+                    proof,
+                    create_buyer_token_account: Self::unpack_trailing_flag(
+                        rest,
+                        26 + proof_len * 32,
+                    )?,
+                    is_gift: Self::unpack_trailing_flag(rest, 27 + proof_len * 32)?,
+                })
+            }
+            16 => Ok(Self::ProposeAuthority {
+                new_authority: Pubkey::new_from_array(Self::unpack_array(rest, 0)?),
+            }),
+            17 => Ok(Self::AcceptAuthority {}),
+            18 => Ok(Self::PauseSale {}),
+            19 => Ok(Self::ResumeSale {}),
+            20 => Ok(Self::MigrateState {}),
+            21 => Ok(Self::SetReceiptsEnabled {
+                receipts_enabled: match Self::unpack_u8(rest, 0)? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(InvalidInstruction.into()),
+                },
+            }),
+            22 => Ok(Self::SetTransferFeeMode {
+                transfer_fee_mode: TransferFeeMode::from_u8(Self::unpack_u8(rest, 0)?)
+                    .ok_or(InvalidInstruction)?,
+            }),
+            23 => Ok(Self::BuyPackage {
+                tier_id: Self::unpack_u8(rest, 0)?,
+                max_total_lamports: Self::unpack_u64(rest, 1)?,
+                expected_price: Self::unpack_u64(rest, 9)?,
+                create_buyer_token_account: Self::unpack_trailing_flag(rest, 17)?,
+                is_gift: Self::unpack_trailing_flag(rest, 18)?,
+            }),
+            24 => Ok(Self::AddTier {
+                tier_id: Self::unpack_u8(rest, 0)?,
+                name: Self::unpack_array(rest, 1)?,
+                sold_tokens: Self::unpack_u64(rest, 1 + TIER_NAME_LEN)?,
+                bonus_tokens: Self::unpack_u64(rest, 9 + TIER_NAME_LEN)?,
+            }),
+            25 => Ok(Self::EditTier {
+                tier_id: Self::unpack_u8(rest, 0)?,
+                name: Self::unpack_array(rest, 1)?,
+                sold_tokens: Self::unpack_u64(rest, 1 + TIER_NAME_LEN)?,
+                bonus_tokens: Self::unpack_u64(rest, 9 + TIER_NAME_LEN)?,
+            }),
+            26 => Ok(Self::EnableTier {
+                tier_id: Self::unpack_u8(rest, 0)?,
+            }),
+            27 => Ok(Self::DisableTier {
+                tier_id: Self::unpack_u8(rest, 0)?,
+            }),
+            28 => Ok(Self::RemoveTier {
+                tier_id: Self::unpack_u8(rest, 0)?,
+            }),
             _ => Err(InvalidInstruction.into()),
         };
     }
+
+    //inverse of `unpack`
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Self::InitSale {
+                new_price_per_token,
+                start_ts,
+                end_ts,
+                sale_id,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&new_price_per_token.to_le_bytes());
+                buf.extend_from_slice(&start_ts.to_le_bytes());
+                buf.extend_from_slice(&end_ts.to_le_bytes());
+                buf.extend_from_slice(&sale_id.to_le_bytes());
+            }
+            Self::UpdatePrice {
+                new_price_per_token,
+            } => {
+                buf.push(1);
+                buf.extend_from_slice(&new_price_per_token.to_le_bytes());
+            }
+            Self::EndTokenSale {} => buf.push(2),
+            Self::BuyPackage {
+                tier_id,
+                max_total_lamports,
+                expected_price,
+                create_buyer_token_account,
+                is_gift,
+            } => {
+                buf.push(23);
+                buf.push(*tier_id);
+                buf.extend_from_slice(&max_total_lamports.to_le_bytes());
+                buf.extend_from_slice(&expected_price.to_le_bytes());
+                buf.push(*create_buyer_token_account as u8);
+                buf.push(*is_gift as u8);
+            }
+            Self::AddTier {
+                tier_id,
+                name,
+                sold_tokens,
+                bonus_tokens,
+            } => {
+                buf.push(24);
+                Self::pack_tier(&mut buf, *tier_id, name, *sold_tokens, *bonus_tokens);
+            }
+            Self::EditTier {
+                tier_id,
+                name,
+                sold_tokens,
+                bonus_tokens,
+            } => {
+                buf.push(25);
+                Self::pack_tier(&mut buf, *tier_id, name, *sold_tokens, *bonus_tokens);
+            }
+            Self::EnableTier { tier_id } => buf.extend_from_slice(&[26, *tier_id]),
+            Self::DisableTier { tier_id } => buf.extend_from_slice(&[27, *tier_id]),
+            Self::RemoveTier { tier_id } => buf.extend_from_slice(&[28, *tier_id]),
+            Self::BuyTokens {
+                amount,
+                max_total_lamports,
+                expected_price,
+                create_buyer_token_account,
+                is_gift,
+            } => {
+                buf.push(9);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&max_total_lamports.to_le_bytes());
+                buf.extend_from_slice(&expected_price.to_le_bytes());
+                buf.push(*create_buyer_token_account as u8);
+                buf.push(*is_gift as u8);
+            }
+            Self::SetBonusSchedule { bonus_schedule } => {
+                buf.push(10);
+                for breakpoint in bonus_schedule {
+                    let mut breakpoint_bytes = [0u8; BonusBreakpoint::LEN];
+                    breakpoint.pack_into_slice(&mut breakpoint_bytes);
+                    buf.extend_from_slice(&breakpoint_bytes);
+                }
+            }
+            Self::SetPaymentMint { new_price_per_token } => {
+                buf.push(11);
+                buf.extend_from_slice(&new_price_per_token.to_le_bytes());
+            }
+            Self::SetSaleWindow { start_ts, end_ts } => {
+                buf.push(12);
+                buf.extend_from_slice(&start_ts.to_le_bytes());
+                buf.extend_from_slice(&end_ts.to_le_bytes());
+            }
+            Self::SetWalletCap {
+                max_tokens_per_wallet,
+            } => {
+                buf.push(13);
+                buf.extend_from_slice(&max_tokens_per_wallet.to_le_bytes());
+            }
+            Self::SetMerkleRoot { merkle_root } => {
+                buf.push(14);
+                buf.extend_from_slice(merkle_root);
+            }
+            Self::BuyPackageWithProof {
+                tier_id,
+                max_total_lamports,
+                expected_price,
+                allocation,
+                proof,
+                create_buyer_token_account,
+                is_gift,
+            } => {
+                buf.push(15);
+                buf.push(*tier_id);
+                buf.extend_from_slice(&max_total_lamports.to_le_bytes());
+                buf.extend_from_slice(&expected_price.to_le_bytes());
+                buf.extend_from_slice(&allocation.to_le_bytes());
+                //builders reject proofs longer than u8::MAX
+                buf.push(proof.len() as u8);
+                for node in proof {
+                    buf.extend_from_slice(node);
+                }
+                buf.push(*create_buyer_token_account as u8);
+                buf.push(*is_gift as u8);
+            }
+            Self::ProposeAuthority { new_authority } => {
+                buf.push(16);
+                buf.extend_from_slice(new_authority.as_ref());
+            }
+            Self::AcceptAuthority {} => buf.push(17),
+            Self::PauseSale {} => buf.push(18),
+            Self::ResumeSale {} => buf.push(19),
+            Self::MigrateState {} => buf.push(20),
+            Self::SetReceiptsEnabled { receipts_enabled } => {
+                buf.extend_from_slice(&[21, *receipts_enabled as u8]);
+            }
+            Self::SetTransferFeeMode { transfer_fee_mode } => {
+                buf.extend_from_slice(&[22, *transfer_fee_mode as u8]);
+            }
+        }
+        return buf;
+    }
+
+    fn pack_tier(
+        buf: &mut Vec<u8>,
+        tier_id: u8,
+        name: &[u8; TIER_NAME_LEN],
+        sold_tokens: u64,
+        bonus_tokens: u64,
+    ) {
+        buf.push(tier_id);
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(&sold_tokens.to_le_bytes());
+        buf.extend_from_slice(&bonus_tokens.to_le_bytes());
+    }
+
     fn unpack_byte(input: &[u8], byte_index: usize) -> Result<u64, ProgramError> {
         let start_bit = byte_index * 8;
         let end_bit = start_bit + 8;
@@ -53,4 +368,484 @@ impl Batch0SaleInstruction {
 
         return Ok(data);
     }
+
+    fn unpack_u8(input: &[u8], offset: usize) -> Result<u8, ProgramError> {
+        let data = input.get(offset).copied().ok_or(InvalidInstruction)?;
+
+        return Ok(data);
+    }
+
+    fn unpack_u64(input: &[u8], offset: usize) -> Result<u64, ProgramError> {
+        return Ok(u64::from_le_bytes(Self::unpack_array(input, offset)?));
+    }
+
+    //flags appended after a variant's original fields; absent reads as false so
+    //instruction data from older clients still decodes
+    fn unpack_trailing_flag(input: &[u8], offset: usize) -> Result<bool, ProgramError> {
+        return match input.get(offset) {
+            None | Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            Some(_) => Err(InvalidInstruction.into()),
+        };
+    }
+
+    fn unpack_array<const N: usize>(input: &[u8], offset: usize) -> Result<[u8; N], ProgramError> {
+        let data = input
+            .get(offset..offset + N)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(InvalidInstruction)?;
+
+        return Ok(data);
+    }
+}
+
+//builders below lay out accounts in the order each processor function reads
+//them with `next_account_info`, deriving the program's PDAs along the way.
+//For a mint with a TransferHook, pass the hook program, its extra-account-metas
+//PDA and the accounts that PDA lists as `extra_accounts`; the program resolves
+//the hook's accounts from them on-chain
+
+//creates the sale state at its PDA; the shelf signs to hand itself to the sale
+#[allow(clippy::too_many_arguments)]
+pub fn init_sale(
+    program_id: &Pubkey,
+    inventory: &Pubkey,
+    till: &Pubkey,
+    shelf: &Pubkey,
+    mint: &Pubkey,
+    new_price_per_token: u64,
+    start_ts: i64,
+    end_ts: i64,
+    sale_id: u64,
+) -> Instruction {
+    let (sale, _) = find_sale_state_address(mint, inventory, sale_id, program_id);
+
+    return Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*inventory, true),
+            AccountMeta::new_readonly(*till, false),
+            AccountMeta::new(*shelf, true),
+            AccountMeta::new(sale, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token_2022::id(), false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+        data: Batch0SaleInstruction::InitSale {
+            new_price_per_token,
+            start_ts,
+            end_ts,
+            sale_id,
+        }
+        .pack(),
+    };
+}
+
+//any instruction that goes through the processor's `update_sale`
+pub fn update_sale(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    sale: &Pubkey,
+    instruction: Batch0SaleInstruction,
+) -> Instruction {
+    return Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*sale, false),
+        ],
+        data: instruction.pack(),
+    };
+}
+
+pub fn update_price(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    sale: &Pubkey,
+    new_price_per_token: u64,
+) -> Instruction {
+    return update_sale(
+        program_id,
+        authority,
+        sale,
+        Batch0SaleInstruction::UpdatePrice {
+            new_price_per_token,
+        },
+    );
+}
+
+//`payment_mint` None switches the sale back to lamports; `new_price_per_token`
+//is in base units of whichever currency the sale switches to
+pub fn set_payment_mint(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    sale: &Pubkey,
+    till: &Pubkey,
+    payment_mint: Option<&Pubkey>,
+    new_price_per_token: u64,
+) -> Instruction {
+    let mut instruction = update_sale(
+        program_id,
+        authority,
+        sale,
+        Batch0SaleInstruction::SetPaymentMint { new_price_per_token },
+    );
+    instruction.accounts.push(AccountMeta::new_readonly(*till, false));
+    if let Some(payment_mint) = payment_mint {
+        instruction.accounts.push(AccountMeta::new_readonly(*payment_mint, false));
+    }
+
+    return instruction;
+}
+
+pub fn accept_authority(
+    program_id: &Pubkey,
+    pending_authority: &Pubkey,
+    sale: &Pubkey,
+) -> Instruction {
+    return Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*pending_authority, true),
+            AccountMeta::new(*sale, false),
+        ],
+        data: Batch0SaleInstruction::AcceptAuthority {}.pack(),
+    };
+}
+
+//sweeps the shelf into `inventory_token_account` and closes the sale to `inventory`
+pub fn end_token_sale(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    sale: &Pubkey,
+    sale_data: &Batch0SaleProgramData,
+    inventory_token_account: &Pubkey,
+    extra_accounts: &[AccountMeta],
+) -> Instruction {
+    let (sale_authority, _) = find_sale_authority_address(sale, program_id);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*sale, false),
+        AccountMeta::new(sale_data.inventory_pubkey, false),
+        AccountMeta::new(sale_data.shelf_pubkey, false),
+        AccountMeta::new_readonly(sale_data.mint_pubkey, false),
+        AccountMeta::new(*inventory_token_account, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(sale_authority, false),
+    ];
+    accounts.extend_from_slice(extra_accounts);
+
+    return Instruction {
+        program_id: *program_id,
+        accounts,
+        data: Batch0SaleInstruction::EndTokenSale {}.pack(),
+    };
+}
+
+pub fn migrate_state(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    sale: &Pubkey,
+    shelf: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    let (legacy_sale_authority, _) = find_legacy_sale_authority_address(program_id);
+
+    return Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*sale, false),
+            AccountMeta::new(*shelf, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(legacy_sale_authority, false),
+            AccountMeta::new_readonly(spl_token_2022::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: Batch0SaleInstruction::MigrateState {}.pack(),
+    };
+}
+
+//payer side of a sale priced in a payment mint
+pub struct PaymentAccounts {
+    pub buyer_payment_account: Pubkey,
+    pub payment_token_program: Pubkey,
+}
+
+//accounts shared by BuyPackage, BuyPackageWithProof and BuyTokens
+pub struct BuyAccounts {
+    pub buyer: Pubkey,
+    pub sale: Pubkey,
+    pub buyer_token_account: Pubkey,
+    //the buyer record's `purchase_count`, 0 before the first purchase; only
+    //read when the sale has receipts enabled
+    pub purchase_index: u64,
+    //required when the sale has a payment mint
+    pub payment: Option<PaymentAccounts>,
+    //creates `buyer_token_account` as the buyer's (or the recipient's)
+    //associated token account if it does not exist yet, with the buyer paying
+    //its rent
+    pub create_buyer_token_account: bool,
+    //gift purchases: the buyer pays and the tokens go to this wallet, with
+    //`buyer_token_account` set to its associated token account
+    pub recipient: Option<Pubkey>,
+    //the mint's transfer hook accounts, empty unless it has a TransferHook
+    pub extra_accounts: Vec<AccountMeta>,
+}
+
+impl BuyAccounts {
+    pub fn to_account_metas(
+        &self,
+        program_id: &Pubkey,
+        sale_data: &Batch0SaleProgramData,
+    ) -> Vec<AccountMeta> {
+        let (sale_authority, _) = find_sale_authority_address(&self.sale, program_id);
+        let (buyer_record, _) = find_buyer_record_address(&self.sale, &self.buyer, program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(self.buyer, true),
+            AccountMeta::new(self.sale, false),
+            AccountMeta::new(sale_data.till_pubkey, false),
+            AccountMeta::new(sale_data.shelf_pubkey, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(self.buyer_token_account, false),
+        ];
+        if let Some(recipient) = &self.recipient {
+            accounts.push(AccountMeta::new_readonly(*recipient, false));
+        }
+        accounts.push(AccountMeta::new_readonly(spl_token_2022::id(), false));
+        accounts.push(AccountMeta::new_readonly(sale_data.mint_pubkey, false));
+        if self.create_buyer_token_account {
+            accounts.push(AccountMeta::new_readonly(
+                spl_associated_token_account_client::program::id(),
+                false,
+            ));
+        }
+        accounts.push(AccountMeta::new_readonly(sale_authority, false));
+        accounts.push(AccountMeta::new(buyer_record, false));
+        if sale_data.receipts_enabled {
+            let (receipt, _) =
+                find_receipt_address(&self.sale, &self.buyer, self.purchase_index, program_id);
+            accounts.push(AccountMeta::new(receipt, false));
+        }
+        if let Some(payment) = &self.payment {
+            accounts.push(AccountMeta::new(payment.buyer_payment_account, false));
+            accounts.push(AccountMeta::new_readonly(sale_data.payment_mint_pubkey, false));
+            accounts.push(AccountMeta::new_readonly(payment.payment_token_program, false));
+        }
+        accounts.extend_from_slice(&self.extra_accounts);
+
+        return accounts;
+    }
+}
+
+pub fn buy_package(
+    program_id: &Pubkey,
+    accounts: &BuyAccounts,
+    sale_data: &Batch0SaleProgramData,
+    tier_id: u8,
+    max_total_lamports: u64,
+    expected_price: u64,
+) -> Instruction {
+    return Instruction {
+        program_id: *program_id,
+        accounts: accounts.to_account_metas(program_id, sale_data),
+        data: Batch0SaleInstruction::BuyPackage {
+            tier_id,
+            max_total_lamports,
+            expected_price,
+            create_buyer_token_account: accounts.create_buyer_token_account,
+            is_gift: accounts.recipient.is_some(),
+        }
+        .pack(),
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn buy_package_with_proof(
+    program_id: &Pubkey,
+    accounts: &BuyAccounts,
+    sale_data: &Batch0SaleProgramData,
+    tier_id: u8,
+    max_total_lamports: u64,
+    expected_price: u64,
+    allocation: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    if proof.len() > u8::MAX as usize {
+        return Err(InvalidInstruction.into());
+    }
+
+    return Ok(Instruction {
+        program_id: *program_id,
+        accounts: accounts.to_account_metas(program_id, sale_data),
+        data: Batch0SaleInstruction::BuyPackageWithProof {
+            tier_id,
+            max_total_lamports,
+            expected_price,
+            allocation,
+            proof,
+            create_buyer_token_account: accounts.create_buyer_token_account,
+            is_gift: accounts.recipient.is_some(),
+        }
+        .pack(),
+    });
+}
+
+pub fn buy_tokens(
+    program_id: &Pubkey,
+    accounts: &BuyAccounts,
+    sale_data: &Batch0SaleProgramData,
+    amount: u64,
+    max_total_lamports: u64,
+    expected_price: u64,
+) -> Instruction {
+    return Instruction {
+        program_id: *program_id,
+        accounts: accounts.to_account_metas(program_id, sale_data),
+        data: Batch0SaleInstruction::BuyTokens {
+            amount,
+            max_total_lamports,
+            expected_price,
+            create_buyer_token_account: accounts.create_buyer_token_account,
+            is_gift: accounts.recipient.is_some(),
+        }
+        .pack(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_round_trips() {
+        let name = *b"Barrel\0\0\0\0\0\0\0\0\0\0";
+        let instructions = vec![
+            Batch0SaleInstruction::InitSale {
+                new_price_per_token: 2,
+                start_ts: -1,
+                end_ts: 1_700_000_000,
+                sale_id: 7,
+            },
+            Batch0SaleInstruction::UpdatePrice {
+                new_price_per_token: 3,
+            },
+            Batch0SaleInstruction::EndTokenSale {},
+            Batch0SaleInstruction::BuyPackage {
+                tier_id: 5,
+                max_total_lamports: 1_000,
+                expected_price: 2,
+                create_buyer_token_account: true,
+                is_gift: true,
+            },
+            Batch0SaleInstruction::AddTier {
+                tier_id: 6,
+                name,
+                sold_tokens: 1_500_000,
+                bonus_tokens: 375_000,
+            },
+            Batch0SaleInstruction::EditTier {
+                tier_id: 6,
+                name,
+                sold_tokens: 1_600_000,
+                bonus_tokens: 400_000,
+            },
+            Batch0SaleInstruction::EnableTier { tier_id: 6 },
+            Batch0SaleInstruction::DisableTier { tier_id: 6 },
+            Batch0SaleInstruction::RemoveTier { tier_id: 6 },
+            Batch0SaleInstruction::BuyTokens {
+                amount: 2_500,
+                max_total_lamports: u64::MAX,
+                expected_price: 0,
+                create_buyer_token_account: false,
+                is_gift: true,
+            },
+            Batch0SaleInstruction::SetBonusSchedule {
+                bonus_schedule: BonusBreakpoint::default_schedule(),
+            },
+            Batch0SaleInstruction::SetPaymentMint {
+                new_price_per_token: 25,
+            },
+            Batch0SaleInstruction::SetSaleWindow {
+                start_ts: 1_600_000_000,
+                end_ts: 0,
+            },
+            Batch0SaleInstruction::SetWalletCap {
+                max_tokens_per_wallet: 10_000,
+            },
+            Batch0SaleInstruction::SetMerkleRoot {
+                merkle_root: [9; 32],
+            },
+            Batch0SaleInstruction::BuyPackageWithProof {
+                tier_id: 1,
+                max_total_lamports: 5_000,
+                expected_price: 2,
+                allocation: 1_000,
+                proof: vec![[1; 32], [2; 32], [3; 32]],
+                create_buyer_token_account: true,
+                is_gift: false,
+            },
+            Batch0SaleInstruction::BuyPackageWithProof {
+                tier_id: 1,
+                max_total_lamports: 5_000,
+                expected_price: 2,
+                allocation: 0,
+                proof: vec![],
+                create_buyer_token_account: false,
+                is_gift: true,
+            },
+            Batch0SaleInstruction::ProposeAuthority {
+                new_authority: Pubkey::new_from_array([4; 32]),
+            },
+            Batch0SaleInstruction::AcceptAuthority {},
+            Batch0SaleInstruction::PauseSale {},
+            Batch0SaleInstruction::ResumeSale {},
+            Batch0SaleInstruction::MigrateState {},
+            Batch0SaleInstruction::SetReceiptsEnabled {
+                receipts_enabled: true,
+            },
+            Batch0SaleInstruction::SetTransferFeeMode {
+                transfer_fee_mode: TransferFeeMode::ChargeBuyer,
+            },
+        ];
+
+        for instruction in instructions {
+            assert_eq!(Batch0SaleInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn trailing_flags_default_to_false_for_older_clients() {
+        let mut data = Batch0SaleInstruction::BuyPackage {
+            tier_id: 2,
+            max_total_lamports: 1_000,
+            expected_price: 2,
+            create_buyer_token_account: true,
+            is_gift: true,
+        }
+        .pack();
+        data.truncate(data.len() - 2);
+
+        assert_eq!(
+            Batch0SaleInstruction::unpack(&data).unwrap(),
+            Batch0SaleInstruction::BuyPackage {
+                tier_id: 2,
+                max_total_lamports: 1_000,
+                expected_price: 2,
+                create_buyer_token_account: false,
+                is_gift: false,
+            }
+        );
+    }
+
+    #[test]
+    fn retired_package_tags_are_rejected() {
+        for tag in 3..=8 {
+            assert!(Batch0SaleInstruction::unpack(&[tag]).is_err());
+        }
+    }
 }
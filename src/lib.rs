@@ -1,7 +1,13 @@
+#![allow(clippy::needless_return)]
+
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod merkle;
+pub mod pda;
 pub mod processor;
 pub mod state;
+pub mod validation;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
@@ -0,0 +1,79 @@
+use solana_keccak_hasher::hashv;
+use solana_program::pubkey::Pubkey;
+
+//leaf committed to by the presale allowlist root; an allocation of 0 means the
+//buyer is allowlisted without a personal cap
+pub fn allowlist_leaf(buyer_pubkey: &Pubkey, allocation: u64) -> [u8; 32] {
+    return hashv(&[buyer_pubkey.as_ref(), &allocation.to_le_bytes()]).to_bytes();
+}
+
+//sorted-pair keccak merkle proof, so proofs carry no left/right flags
+pub fn verify_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+
+    return computed == *root;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        return hashv(&[&low, &high]).to_bytes();
+    }
+
+    fn leaves() -> [[u8; 32]; 4] {
+        return [
+            allowlist_leaf(&Pubkey::new_from_array([1; 32]), 0),
+            allowlist_leaf(&Pubkey::new_from_array([2; 32]), 1_500_000),
+            allowlist_leaf(&Pubkey::new_from_array([3; 32]), 0),
+            allowlist_leaf(&Pubkey::new_from_array([4; 32]), 7_000_000),
+        ];
+    }
+
+    #[test]
+    fn verifies_every_leaf_of_a_four_leaf_tree() {
+        let [a, b, c, d] = leaves();
+        let (ab, cd) = (parent(a, b), parent(c, d));
+        let root = parent(ab, cd);
+
+        assert!(verify_proof(&[b, cd], &root, a));
+        assert!(verify_proof(&[a, cd], &root, b));
+        assert!(verify_proof(&[d, ab], &root, c));
+        assert!(verify_proof(&[c, ab], &root, d));
+    }
+
+    #[test]
+    fn rejects_a_tampered_proof_or_leaf() {
+        let [a, b, c, d] = leaves();
+        let cd = parent(c, d);
+        let root = parent(parent(a, b), cd);
+
+        let mut tampered = b;
+        tampered[0] ^= 1;
+        assert!(!verify_proof(&[tampered, cd], &root, a));
+        assert!(!verify_proof(&[b], &root, a));
+
+        //same buyer, claiming a different allocation
+        let inflated = allowlist_leaf(&Pubkey::new_from_array([2; 32]), 9_000_000);
+        assert!(!verify_proof(&[a, cd], &root, inflated));
+    }
+
+    #[test]
+    fn hashes_pairs_in_sorted_order() {
+        let [a, b, ..] = leaves();
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        let root = hashv(&[&low, &high]).to_bytes();
+
+        assert!(verify_proof(&[high], &root, low));
+        assert!(verify_proof(&[low], &root, high));
+        assert!(!verify_proof(&[low], &hashv(&[&high, &low]).to_bytes(), high));
+    }
+}
@@ -0,0 +1,86 @@
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+//seed prefix for the PDA that owns a sale's shelf
+pub const SALE_AUTHORITY_SEED: &[u8] = b"batch0_sale";
+
+//each sale state account gets its own shelf authority, so several sales can
+//run side by side under one program id
+pub fn find_sale_authority_address(sale_pubkey: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    return Pubkey::find_program_address(&[SALE_AUTHORITY_SEED, sale_pubkey.as_ref()], program_id);
+}
+
+pub fn create_sale_authority_address(
+    sale_pubkey: &Pubkey,
+    bump_seed: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    return Pubkey::create_program_address(
+        &[SALE_AUTHORITY_SEED, sale_pubkey.as_ref(), &[bump_seed]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds);
+}
+
+//seed prefix for a buyer's purchase record within one sale
+pub const BUYER_RECORD_SEED: &[u8] = b"buyer";
+
+pub fn find_buyer_record_address(
+    sale_pubkey: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    return Pubkey::find_program_address(
+        &[BUYER_RECORD_SEED, sale_pubkey.as_ref(), buyer_pubkey.as_ref()],
+        program_id,
+    );
+}
+
+//seed prefix for the receipt of a buyer's nth purchase within one sale
+pub const RECEIPT_SEED: &[u8] = b"receipt";
+
+pub fn find_receipt_address(
+    sale_pubkey: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    purchase_index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    return Pubkey::find_program_address(
+        &[
+            RECEIPT_SEED,
+            sale_pubkey.as_ref(),
+            buyer_pubkey.as_ref(),
+            &purchase_index.to_le_bytes(),
+        ],
+        program_id,
+    );
+}
+
+//v1 sales shared a single shelf authority seeded only by SALE_AUTHORITY_SEED;
+//MigrateState signs with it once to hand the shelf to the per-sale PDA
+pub fn find_legacy_sale_authority_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    return Pubkey::find_program_address(&[SALE_AUTHORITY_SEED], program_id);
+}
+
+//seed prefix for a sale's state account; InitSale creates it at this address
+pub const SALE_STATE_SEED: &[u8] = b"sale_state";
+
+//keyed by mint, the inventory that created the sale and a caller-chosen sale
+//id, so a client holding the mint and the seller's inventory wallet can walk
+//sale ids 0, 1, .. to find every sale of it; the inventory has to sign
+//InitSale, so nobody else can claim those ids
+pub fn find_sale_state_address(
+    mint_pubkey: &Pubkey,
+    inventory_pubkey: &Pubkey,
+    sale_id: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    return Pubkey::find_program_address(
+        &[
+            SALE_STATE_SEED,
+            mint_pubkey.as_ref(),
+            inventory_pubkey.as_ref(),
+            &sale_id.to_le_bytes(),
+        ],
+        program_id,
+    );
+}
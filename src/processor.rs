@@ -1,22 +1,61 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::Instruction,
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
     system_instruction,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
+use spl_associated_token_account_client::{
+    address::get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
 use spl_token_2022::ID as TOKEN_2022_PROGRAM_ID;
+use spl_transfer_hook_interface::onchain::add_extra_accounts_for_execute_cpi;
 use spl_token_2022::{
-    extension::StateWithExtensions,
+    extension::{
+        transfer_fee::{instruction as transfer_fee_instruction, TransferFeeConfig},
+        transfer_hook,
+        BaseStateWithExtensions, StateWithExtensions,
+    },
     state::{Account, Mint},
 };
 
-use crate::{instruction::Batch0SaleInstruction, state::Batch0SaleProgramData};
+use crate::{
+    error::CustomError::{
+        AllocationExceeded, IncorrectPaymentTokenProgram, InvalidBonusSchedule, InvalidBuyerRecord,
+        InvalidInventoryAccount, InvalidMerkleProof, InvalidMintAccount, InvalidPaymentMint,
+        InvalidPurchaseAmount, InvalidReceiptAccount, InvalidRecipientTokenAccount,
+        InvalidSaleAuthority, InvalidSaleStateAccount, InvalidSaleWindow, InvalidShelfAccount,
+        InvalidTier, InvalidTillAccount, InvalidTokenAccount, MintMismatch,
+        MissingAuthoritySignature, NoPendingAuthority, PresaleProofRequired, PriceChanged,
+        SaleEnded, SaleNotStarted, SalePaused, StateAlreadyCurrent, StateMigrationRequired,
+        TierAlreadyExists, TierDisabled, WalletCapExceeded, WrongAuthority,
+    },
+    event::Batch0SaleEvent,
+    instruction::Batch0SaleInstruction,
+    validation::{
+        assert_associated_token_program, assert_keys_equal, assert_mint, assert_owned_by,
+        assert_signer, assert_system_program, assert_token_2022_program, assert_token_account,
+        assert_writable,
+    },
+    merkle::{allowlist_leaf, verify_proof},
+    pda::{
+        create_sale_authority_address, find_buyer_record_address,
+        find_legacy_sale_authority_address, find_sale_authority_address, find_receipt_address,
+        find_sale_state_address, BUYER_RECORD_SEED, RECEIPT_SEED, SALE_AUTHORITY_SEED,
+        SALE_STATE_SEED,
+    },
+    state::{
+        Batch0SaleProgramData, BonusBreakpoint, BuyerRecord, PackageTier, PurchaseReceipt,
+        SaleStatus, TransferFeeMode, BONUS_BPS_DENOMINATOR, MAX_BONUS_BREAKPOINTS, STATE_VERSION,
+    },
+};
 pub struct Processor;
 impl Processor {
     pub fn process(
@@ -27,54 +66,255 @@ impl Processor {
         let instruction = Batch0SaleInstruction::unpack(instruction_data)?;
 
         match instruction {
-            Batch0SaleInstruction::InitSale { new_price_per_token } => {
+            Batch0SaleInstruction::InitSale {
+                new_price_per_token,
+                start_ts,
+                end_ts,
+                sale_id,
+            } => {
                 msg!("Instruction: init token sale program");
-                Self::init_sale(accounts, new_price_per_token, batch0_sale_program_id)
+                Self::init_sale(
+                    accounts,
+                    new_price_per_token,
+                    start_ts,
+                    end_ts,
+                    sale_id,
+                    batch0_sale_program_id,
+                )
             }
             Batch0SaleInstruction::UpdatePrice { new_price_per_token } => {
-                msg!("Instruction: init token sale program");
-                Self::update_price(accounts, new_price_per_token)
+                msg!("Instruction : update price");
+                Self::update_price(accounts, batch0_sale_program_id, new_price_per_token)
             }
             Batch0SaleInstruction::EndTokenSale {} => {
                 msg!("Instruction : end token sale");
                 Self::end_token_sale(accounts, batch0_sale_program_id)
             }
-            Batch0SaleInstruction::BuyShot {} => {
-                msg!("Instruction : buy a shot ");
-                Self::buy_tokens(accounts, batch0_sale_program_id, 1000, 0)
+            Batch0SaleInstruction::BuyPackage {
+                tier_id,
+                max_total_lamports,
+                expected_price,
+                create_buyer_token_account,
+                is_gift,
+            } => {
+                msg!("Instruction : buy package tier {}", tier_id);
+                Self::buy_tokens(
+                    accounts,
+                    batch0_sale_program_id,
+                    |sale| Self::resolve_tier(sale, tier_id),
+                    max_total_lamports,
+                    expected_price,
+                    None,
+                    create_buyer_token_account,
+                    is_gift,
+                )
+            }
+            Batch0SaleInstruction::BuyPackageWithProof {
+                tier_id,
+                max_total_lamports,
+                expected_price,
+                allocation,
+                proof,
+                create_buyer_token_account,
+                is_gift,
+            } => {
+                msg!("Instruction : presale buy package tier {}", tier_id);
+                Self::buy_tokens(
+                    accounts,
+                    batch0_sale_program_id,
+                    |sale| Self::resolve_tier(sale, tier_id),
+                    max_total_lamports,
+                    expected_price,
+                    Some((allocation, &proof)),
+                    create_buyer_token_account,
+                    is_gift,
+                )
+            }
+            Batch0SaleInstruction::BuyTokens {
+                amount,
+                max_total_lamports,
+                expected_price,
+                create_buyer_token_account,
+                is_gift,
+            } => {
+                msg!("Instruction : buy {} tokens", amount);
+                Self::buy_tokens(
+                    accounts,
+                    batch0_sale_program_id,
+                    |sale| {
+                        if amount == 0 {
+                            return Err(InvalidPurchaseAmount.into());
+                        }
+                        let bonus_tokens =
+                            sale.bonus_for(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+                        return Ok((amount, bonus_tokens, None));
+                    },
+                    max_total_lamports,
+                    expected_price,
+                    None,
+                    create_buyer_token_account,
+                    is_gift,
+                )
+            }
+            Batch0SaleInstruction::SetBonusSchedule { bonus_schedule } => {
+                msg!("Instruction : set bonus schedule");
+                Self::set_bonus_schedule(accounts, batch0_sale_program_id, bonus_schedule)
+            }
+            Batch0SaleInstruction::SetPaymentMint { new_price_per_token } => {
+                msg!("Instruction : set payment mint");
+                Self::set_payment_mint(accounts, batch0_sale_program_id, new_price_per_token)
+            }
+            Batch0SaleInstruction::SetSaleWindow { start_ts, end_ts } => {
+                msg!("Instruction : set sale window");
+                Self::set_sale_window(accounts, batch0_sale_program_id, start_ts, end_ts)
+            }
+            Batch0SaleInstruction::SetWalletCap {
+                max_tokens_per_wallet,
+            } => {
+                msg!("Instruction : set per-wallet cap");
+                Self::set_wallet_cap(accounts, batch0_sale_program_id, max_tokens_per_wallet)
+            }
+            Batch0SaleInstruction::SetMerkleRoot { merkle_root } => {
+                msg!("Instruction : set presale merkle root");
+                Self::set_merkle_root(accounts, batch0_sale_program_id, merkle_root)
+            }
+            Batch0SaleInstruction::AddTier {
+                tier_id,
+                name,
+                sold_tokens,
+                bonus_tokens,
+            } => {
+                msg!("Instruction : add package tier {}", tier_id);
+                Self::update_tier(accounts, batch0_sale_program_id, tier_id, |tier| {
+                    //a tier must sell something, as BuyTokens must
+                    if sold_tokens == 0 {
+                        return Err(InvalidPurchaseAmount.into());
+                    }
+                    if tier.is_initialized {
+                        return Err(TierAlreadyExists.into());
+                    }
+                    *tier = PackageTier {
+                        is_initialized: true,
+                        is_enabled: true,
+                        name,
+                        sold_tokens,
+                        bonus_tokens,
+                        purchase_count: 0,
+                    };
+                    return Ok(());
+                })
+            }
+            Batch0SaleInstruction::EditTier {
+                tier_id,
+                name,
+                sold_tokens,
+                bonus_tokens,
+            } => {
+                msg!("Instruction : edit package tier {}", tier_id);
+                Self::update_tier(accounts, batch0_sale_program_id, tier_id, |tier| {
+                    if sold_tokens == 0 {
+                        return Err(InvalidPurchaseAmount.into());
+                    }
+                    if !tier.is_initialized {
+                        return Err(InvalidTier.into());
+                    }
+                    tier.name = name;
+                    tier.sold_tokens = sold_tokens;
+                    tier.bonus_tokens = bonus_tokens;
+                    return Ok(());
+                })
+            }
+            Batch0SaleInstruction::EnableTier { tier_id } => {
+                msg!("Instruction : enable package tier {}", tier_id);
+                Self::update_tier(accounts, batch0_sale_program_id, tier_id, |tier| {
+                    if !tier.is_initialized {
+                        return Err(InvalidTier.into());
+                    }
+                    tier.is_enabled = true;
+                    return Ok(());
+                })
             }
-            Batch0SaleInstruction::BuyDouble {} => {
-                msg!("Instruction : buy a shot ");
-                Self::buy_tokens(accounts, batch0_sale_program_id, 2000, 100)
+            Batch0SaleInstruction::DisableTier { tier_id } => {
+                msg!("Instruction : disable package tier {}", tier_id);
+                Self::update_tier(accounts, batch0_sale_program_id, tier_id, |tier| {
+                    if !tier.is_initialized {
+                        return Err(InvalidTier.into());
+                    }
+                    tier.is_enabled = false;
+                    return Ok(());
+                })
             }
-            Batch0SaleInstruction::BuyFlask {} => {
-                msg!("Instruction : buy a shot ");
-                Self::buy_tokens(accounts, batch0_sale_program_id, 5000, 500)
+            Batch0SaleInstruction::RemoveTier { tier_id } => {
+                msg!("Instruction : remove package tier {}", tier_id);
+                Self::update_tier(accounts, batch0_sale_program_id, tier_id, |tier| {
+                    if !tier.is_initialized {
+                        return Err(InvalidTier.into());
+                    }
+                    *tier = PackageTier::default();
+                    return Ok(());
+                })
             }
-            Batch0SaleInstruction::BuyFifth {} => {
-                msg!("Instruction : buy a shot ");
-                Self::buy_tokens(accounts, batch0_sale_program_id, 25000, 5000)
+            Batch0SaleInstruction::ProposeAuthority { new_authority } => {
+                msg!("Instruction : propose sale authority {}", new_authority);
+                Self::update_sale(accounts, batch0_sale_program_id, |sale, _| {
+                    sale.pending_authority = new_authority;
+                    return Ok(());
+                })
             }
-            Batch0SaleInstruction::BuyCase {} => {
-                msg!("Instruction : buy a shot ");
-                Self::buy_tokens(accounts, batch0_sale_program_id, 300000, 70000)
+            Batch0SaleInstruction::AcceptAuthority {} => {
+                msg!("Instruction : accept sale authority");
+                Self::accept_authority(accounts, batch0_sale_program_id)
             }
-            Batch0SaleInstruction::BuyBarrel {} => {
-                msg!("Instruction : buy a shot ");
-                Self::buy_tokens(accounts, batch0_sale_program_id, 1500000, 375000)
+            Batch0SaleInstruction::PauseSale {} => {
+                msg!("Instruction : pause sale");
+                Self::update_sale(accounts, batch0_sale_program_id, |sale, _| {
+                    sale.status = SaleStatus::Paused;
+                    return Ok(());
+                })
+            }
+            Batch0SaleInstruction::ResumeSale {} => {
+                msg!("Instruction : resume sale");
+                Self::update_sale(accounts, batch0_sale_program_id, |sale, _| {
+                    sale.status = SaleStatus::Active;
+                    return Ok(());
+                })
+            }
+            Batch0SaleInstruction::MigrateState {} => {
+                msg!("Instruction : migrate sale state");
+                Self::migrate_state(accounts, batch0_sale_program_id)
+            }
+            Batch0SaleInstruction::SetReceiptsEnabled { receipts_enabled } => {
+                msg!("Instruction : set purchase receipts");
+                Self::update_sale(accounts, batch0_sale_program_id, |sale, _| {
+                    sale.receipts_enabled = receipts_enabled;
+                    return Ok(());
+                })
+            }
+            Batch0SaleInstruction::SetTransferFeeMode { transfer_fee_mode } => {
+                msg!("Instruction : set transfer fee mode {:?}", transfer_fee_mode);
+                Self::update_sale(accounts, batch0_sale_program_id, |sale, _| {
+                    sale.transfer_fee_mode = transfer_fee_mode;
+                    return Ok(());
+                })
             }
         }
     }
 
-    //inventory account info - Primary owner of the inventory
+    //inventory account info - Primary owner of the inventory, pays for the sale state account
     //shelf ATA - Assignable ssociated Token Account holding the sellable inventory
     //till account info - receives tokens from sales
-    //batch0 sale (token sale) program account info - Save the data about token sale
-    //rent - To check if the rent fee is exempted
+    //batch0 sale (token sale) program account info - Uncreated PDA from find_sale_state_address
+    //system program - To create the sale state account
     //token 2022 program - To faciliate the change in ownership with the mint program
+    //mint - Mint held by the shelf, recorded in the sale state
+    // start_ts / end_ts - Unix timestamps bounding the sale, 0 leaves that side open
+    // sale_id - Distinguishes several sales of the same mint by the same inventory
     fn init_sale(
         account_info_list: &[AccountInfo],
         new_price_per_token: u64,
+        start_ts: i64,
+        end_ts: i64,
+        sale_id: u64,
         batch0_sale_program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut account_info_list.iter();
@@ -84,56 +324,108 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData)
         }
 
+        Self::check_sale_window(start_ts, end_ts)?;
+
+        //inventory pays for the state account
         let inventory_account_info = next_account_info(account_info_iter)?;
-        if !inventory_account_info.is_signer {
-            msg!("Inventory Account must be a signer");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        assert_signer(inventory_account_info)?;
+        assert_writable(inventory_account_info)?;
 
         msg!("Obtaining Till Account");
         let till_account_info = next_account_info(account_info_iter)?;
 
         msg!("Obtaining Shelf Account");
         let shelf_account_info = next_account_info(account_info_iter)?;
+        assert_writable(shelf_account_info)?;
+        assert_owned_by(shelf_account_info, &TOKEN_2022_PROGRAM_ID)?;
 
         msg!("Obtaining Prorgram Data Account");
         let batch0_sale_program_account_info = next_account_info(account_info_iter)?;
+        assert_writable(batch0_sale_program_account_info)?;
 
-        let rent_account_info = next_account_info(account_info_iter)?;
-        let rent = &Rent::from_account_info(rent_account_info)?;
+        let system_program = next_account_info(account_info_iter)?;
+        assert_system_program(system_program)?;
 
-        if !rent.is_exempt(
-            batch0_sale_program_account_info.lamports(),
-            batch0_sale_program_account_info.data_len(),
-        ) {
-            return Err(ProgramError::AccountNotRentExempt);
-        }
+        let token_2022_account_info = next_account_info(account_info_iter)?;
+        assert_token_2022_program(token_2022_account_info)?;
 
-        //get data from account (needed `is_writable = true` option)
-        msg!("Initializing Program Data");
-        let mut batch0_sale_program_account_data = Batch0SaleProgramData::unpack_unchecked(
-            &batch0_sale_program_account_info.try_borrow_data()?,
+        msg!("Obtaining Mint Account");
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let mint_decimals = assert_mint(token_mint_info)?;
+        assert_token_account(shelf_account_info, token_mint_info.key)?;
+
+        let (state_pubkey, state_bump) = find_sale_state_address(
+            token_mint_info.key,
+            inventory_account_info.key,
+            sale_id,
+            batch0_sale_program_id,
+        );
+        assert_keys_equal(
+            batch0_sale_program_account_info.key,
+            &state_pubkey,
+            InvalidSaleStateAccount,
         )?;
-        if batch0_sale_program_account_data.is_initialized {
+        if !batch0_sale_program_account_info.data_is_empty() {
             return Err(ProgramError::AccountAlreadyInitialized);
         }
 
-        batch0_sale_program_account_data.init(
-            true,
-            new_price_per_token,
-            *inventory_account_info.key,
-            *shelf_account_info.key,
-            *till_account_info.key,
+        msg!("Creating Program Data Account {}", state_pubkey);
+        Self::create_pda_account(
+            batch0_sale_program_id,
+            inventory_account_info,
+            batch0_sale_program_account_info,
+            system_program,
+            Batch0SaleProgramData::LEN,
+            &[
+                SALE_STATE_SEED,
+                token_mint_info.key.as_ref(),
+                inventory_account_info.key.as_ref(),
+                &sale_id.to_le_bytes(),
+                &[state_bump],
+            ],
+        )?;
+
+        let (pda, bump_seed) = find_sale_authority_address(
+            batch0_sale_program_account_info.key,
+            batch0_sale_program_id,
         );
 
+        msg!("Initializing Program Data");
+        let batch0_sale_program_account_data = Batch0SaleProgramData {
+            version: STATE_VERSION,
+            is_initialized: true,
+            current_price_per_token: new_price_per_token,
+            inventory_pubkey: *inventory_account_info.key,
+            shelf_pubkey: *shelf_account_info.key,
+            till_pubkey: *till_account_info.key,
+            mint_pubkey: *token_mint_info.key,
+            mint_decimals,
+            sale_id,
+            state_bump,
+            authority: *inventory_account_info.key,
+            pending_authority: Pubkey::default(),
+            authority_bump: bump_seed,
+            payment_mint_pubkey: Pubkey::default(),
+            start_ts,
+            end_ts,
+            max_tokens_per_wallet: 0,
+            merkle_root: [0u8; 32],
+            status: SaleStatus::Active,
+            receipts_enabled: false,
+            transfer_fee_mode: TransferFeeMode::GrossUp,
+            total_tokens_sold: 0,
+            total_bonus_issued: 0,
+            total_collected: 0,
+            total_purchases: 0,
+            tiers: PackageTier::default_tiers(),
+            bonus_schedule: BonusBreakpoint::default_schedule(),
+        };
+
         Batch0SaleProgramData::pack(
             batch0_sale_program_account_data,
             &mut batch0_sale_program_account_info.try_borrow_mut_data()?,
         )?;
 
-        let (pda, _bump_seed) =
-            Pubkey::find_program_address(&[b"batch0_sale"], batch0_sale_program_id);
-
         msg!("Changing Authority for Shelf Account");
         let set_authority_ix = spl_token_2022::instruction::set_authority(
             &TOKEN_2022_PROGRAM_ID,
@@ -141,51 +433,370 @@ impl Processor {
             Some(&pda),
             spl_token_2022::instruction::AuthorityType::AccountOwner,
             shelf_account_info.key,
-            &[&shelf_account_info.key],
+            &[shelf_account_info.key],
         )?;
 
-        let token_2022_account_info = next_account_info(account_info_iter)?;
-        // if *token_2022_account_info.key != spl_token_2022::id() {
-        //     msg!("Error: Wrong token program account passed.");
-        //     return Err(ProgramError::IncorrectProgramId);
-        // }
         msg!("Change Shelf's Authority : Shelf Account -> Batch0 Program");
         invoke(
             &set_authority_ix,
             &[token_2022_account_info.clone(), shelf_account_info.clone()],
         )?;
 
+        Batch0SaleEvent::SaleInitialized {
+            sale: *batch0_sale_program_account_info.key,
+            mint: *token_mint_info.key,
+            authority: *inventory_account_info.key,
+            sale_id,
+            price_per_token: new_price_per_token,
+            start_ts,
+            end_ts,
+        }
+        .emit();
+
         return Ok(());
     }
 
-    fn update_price(
+    //authority account info - Must sign, and match the sale's authority
+    //batch0 sale program account info - Sale state being updated
+    //remaining accounts are handed to `update`
+    fn update_sale<F>(
         account_info_list: &[AccountInfo],
-        new_price_per_token: u64,
+        batch0_sale_program_id: &Pubkey,
+        update: F,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&mut Batch0SaleProgramData, &[AccountInfo]) -> ProgramResult,
+    {
+        let account_info_iter = &mut account_info_list.iter();
+
+        let authority_account_info = next_account_info(account_info_iter)?;
+
+        msg!("Obtaining Prorgram Data Account");
+        let batch0_sale_account_info = next_account_info(account_info_iter)?;
+        assert_writable(batch0_sale_account_info)?;
+        assert_owned_by(batch0_sale_account_info, batch0_sale_program_id)?;
+        let mut batch0_sale_account_data =
+            Self::load_sale(batch0_sale_account_info)?;
+
+        if !batch0_sale_account_data.is_initialized {
+            msg!("Program has not been initilaized");
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        Self::check_authority(authority_account_info, &batch0_sale_account_data)?;
+
+        let old_price_per_token = batch0_sale_account_data.current_price_per_token;
+        let old_status = batch0_sale_account_data.status;
+        let old_pending_authority = batch0_sale_account_data.pending_authority;
+        let old_tiers = batch0_sale_account_data.tiers;
+        let old_window = (batch0_sale_account_data.start_ts, batch0_sale_account_data.end_ts);
+        let old_payment = (
+            batch0_sale_account_data.payment_mint_pubkey,
+            batch0_sale_account_data.till_pubkey,
+        );
+        let old_max_tokens_per_wallet = batch0_sale_account_data.max_tokens_per_wallet;
+        let old_merkle_root = batch0_sale_account_data.merkle_root;
+        let old_bonus_schedule = batch0_sale_account_data.bonus_schedule;
+        let old_receipts_enabled = batch0_sale_account_data.receipts_enabled;
+        let old_transfer_fee_mode = batch0_sale_account_data.transfer_fee_mode;
+
+        update(&mut batch0_sale_account_data, account_info_iter.as_slice())?;
+
+        //every admin change funnels through here, so events are derived from
+        //what the update actually changed
+        let sale = *batch0_sale_account_info.key;
+        if batch0_sale_account_data.current_price_per_token != old_price_per_token {
+            Batch0SaleEvent::PriceUpdated {
+                sale,
+                old_price_per_token,
+                new_price_per_token: batch0_sale_account_data.current_price_per_token,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.status != old_status {
+            Batch0SaleEvent::StatusChanged {
+                sale,
+                status: batch0_sale_account_data.status,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.pending_authority != old_pending_authority {
+            Batch0SaleEvent::AuthorityProposed {
+                sale,
+                authority: batch0_sale_account_data.authority,
+                pending_authority: batch0_sale_account_data.pending_authority,
+            }
+            .emit();
+        }
+        for (tier_id, (old_tier, tier)) in
+            old_tiers.iter().zip(batch0_sale_account_data.tiers.iter()).enumerate()
+        {
+            if old_tier != tier {
+                Batch0SaleEvent::TierUpdated {
+                    sale,
+                    tier_id: tier_id as u8,
+                }
+                .emit();
+            }
+        }
+        if (batch0_sale_account_data.start_ts, batch0_sale_account_data.end_ts) != old_window {
+            Batch0SaleEvent::SaleWindowChanged {
+                sale,
+                start_ts: batch0_sale_account_data.start_ts,
+                end_ts: batch0_sale_account_data.end_ts,
+            }
+            .emit();
+        }
+        if (
+            batch0_sale_account_data.payment_mint_pubkey,
+            batch0_sale_account_data.till_pubkey,
+        ) != old_payment
+        {
+            Batch0SaleEvent::PaymentMintChanged {
+                sale,
+                payment_mint: batch0_sale_account_data.payment_mint_pubkey,
+                till: batch0_sale_account_data.till_pubkey,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.max_tokens_per_wallet != old_max_tokens_per_wallet {
+            Batch0SaleEvent::WalletCapChanged {
+                sale,
+                max_tokens_per_wallet: batch0_sale_account_data.max_tokens_per_wallet,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.merkle_root != old_merkle_root {
+            Batch0SaleEvent::MerkleRootChanged {
+                sale,
+                merkle_root: batch0_sale_account_data.merkle_root,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.bonus_schedule != old_bonus_schedule {
+            Batch0SaleEvent::BonusScheduleChanged {
+                sale,
+                bonus_schedule: batch0_sale_account_data.bonus_schedule,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.receipts_enabled != old_receipts_enabled {
+            Batch0SaleEvent::ReceiptsEnabledChanged {
+                sale,
+                receipts_enabled: batch0_sale_account_data.receipts_enabled,
+            }
+            .emit();
+        }
+        if batch0_sale_account_data.transfer_fee_mode != old_transfer_fee_mode {
+            Batch0SaleEvent::TransferFeeModeChanged {
+                sale,
+                transfer_fee_mode: batch0_sale_account_data.transfer_fee_mode,
+            }
+            .emit();
+        }
+
+        Batch0SaleProgramData::pack(
+            batch0_sale_account_data,
+            &mut batch0_sale_account_info.try_borrow_mut_data()?,
+        )?;
+
+        return Ok(());
+    }
+
+    //sale state in the current layout; older layouts must go through MigrateState
+    fn load_sale(sale_account_info: &AccountInfo) -> Result<Batch0SaleProgramData, ProgramError> {
+        let sale = Batch0SaleProgramData::unpack_any_version(&sale_account_info.try_borrow_data()?)?;
+        if sale.version != STATE_VERSION {
+            msg!("Sale state is v{}, run MigrateState first", sale.version);
+            return Err(StateMigrationRequired.into());
+        }
+
+        return Ok(sale);
+    }
+
+    //authority account info - Must sign, and match the sale's authority (the v1 inventory)
+    //payer account info - Funds the extra rent for the larger layout
+    //batch0 sale program account info - v1 sale state, reallocated in place
+    //shelf account info - Handed from the legacy shelf authority to the per-sale PDA
+    //mint - Mint held by the shelf, recorded in the migrated state
+    //legacy pda - Shelf authority shared by v1 sales
+    //token 2022 program - To move the shelf's authority
+    //system program - To pay the rent top-up
+    fn migrate_state(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut account_info_list.iter();
 
-        if new_price_per_token == 0 {
-            msg!("Price per token was 0");
-            return Err(ProgramError::InvalidAccountData)
+        let authority_account_info = next_account_info(account_info_iter)?;
+
+        let payer_account_info = next_account_info(account_info_iter)?;
+        assert_signer(payer_account_info)?;
+        assert_writable(payer_account_info)?;
+
+        msg!("Obtaining Prorgram Data Account");
+        let batch0_sale_account_info = next_account_info(account_info_iter)?;
+        assert_writable(batch0_sale_account_info)?;
+        assert_owned_by(batch0_sale_account_info, batch0_sale_program_id)?;
+        let mut batch0_sale_account_data = Batch0SaleProgramData::unpack_any_version(
+            &batch0_sale_account_info.try_borrow_data()?,
+        )?;
+        if batch0_sale_account_data.version == STATE_VERSION {
+            msg!("Sale state is already v{}", STATE_VERSION);
+            return Err(StateAlreadyCurrent.into());
         }
 
-        let inventory_account_info = next_account_info(account_info_iter)?;
-        if !inventory_account_info.is_signer {
-            msg!("Inventory Account must be a signer");
-            return Err(ProgramError::MissingRequiredSignature);
+        Self::check_authority(authority_account_info, &batch0_sale_account_data)?;
+
+        let shelf_account_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(
+            shelf_account_info.key,
+            &batch0_sale_account_data.shelf_pubkey,
+            InvalidShelfAccount,
+        )?;
+        assert_writable(shelf_account_info)?;
+
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let mint_decimals = assert_mint(token_mint_info)?;
+        assert_token_account(shelf_account_info, token_mint_info.key)?;
+
+        let (legacy_pda, legacy_bump) = find_legacy_sale_authority_address(batch0_sale_program_id);
+        let legacy_pda_account_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(legacy_pda_account_info.key, &legacy_pda, InvalidSaleAuthority)?;
+
+        let token_2022_account_info = next_account_info(account_info_iter)?;
+        assert_token_2022_program(token_2022_account_info)?;
+
+        let system_program = next_account_info(account_info_iter)?;
+        assert_system_program(system_program)?;
+
+        let (pda, bump_seed) =
+            find_sale_authority_address(batch0_sale_account_info.key, batch0_sale_program_id);
+
+        let rent = Rent::get()?;
+        let required_lamports = rent
+            .minimum_balance(Batch0SaleProgramData::LEN)
+            .saturating_sub(batch0_sale_account_info.lamports());
+        if required_lamports > 0 {
+            msg!("Topping up {} LAMPORTS of rent", required_lamports);
+            invoke(
+                &system_instruction::transfer(
+                    payer_account_info.key,
+                    batch0_sale_account_info.key,
+                    required_lamports,
+                ),
+                &[
+                    payer_account_info.clone(),
+                    batch0_sale_account_info.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+
+        msg!(
+            "Reallocating sale state {} -> {} bytes",
+            batch0_sale_account_info.data_len(),
+            Batch0SaleProgramData::LEN
+        );
+        batch0_sale_account_info.realloc(Batch0SaleProgramData::LEN, true)?;
+
+        msg!("Change Shelf's Authority : legacy PDA -> sale PDA");
+        let set_authority_ix = spl_token_2022::instruction::set_authority(
+            &TOKEN_2022_PROGRAM_ID,
+            shelf_account_info.key,
+            Some(&pda),
+            spl_token_2022::instruction::AuthorityType::AccountOwner,
+            &legacy_pda,
+            &[&legacy_pda],
+        )?;
+        invoke_signed(
+            &set_authority_ix,
+            &[
+                token_2022_account_info.clone(),
+                shelf_account_info.clone(),
+                legacy_pda_account_info.clone(),
+            ],
+            &[&[SALE_AUTHORITY_SEED, &[legacy_bump]]],
+        )?;
+
+        Batch0SaleEvent::StateMigrated {
+            sale: *batch0_sale_account_info.key,
+            old_version: batch0_sale_account_data.version,
+            new_version: STATE_VERSION,
+        }
+        .emit();
+        batch0_sale_account_data.version = STATE_VERSION;
+        batch0_sale_account_data.mint_pubkey = *token_mint_info.key;
+        batch0_sale_account_data.mint_decimals = mint_decimals;
+        batch0_sale_account_data.authority_bump = bump_seed;
+
+        Batch0SaleProgramData::pack(
+            batch0_sale_account_data,
+            &mut batch0_sale_account_info.try_borrow_mut_data()?,
+        )?;
+
+        return Ok(());
+    }
+
+    fn check_authority(
+        authority_account_info: &AccountInfo,
+        sale: &Batch0SaleProgramData,
+    ) -> ProgramResult {
+        if *authority_account_info.key != sale.authority {
+            msg!("{} is not the sale authority", authority_account_info.key);
+            return Err(WrongAuthority.into());
+        }
+        if !authority_account_info.is_signer {
+            msg!("Sale authority must be a signer");
+            return Err(MissingAuthoritySignature.into());
         }
 
+        return Ok(());
+    }
+
+    //pending authority account info - Must sign, and match the proposed authority
+    //batch0 sale program account info - Sale state being handed over
+    fn accept_authority(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut account_info_list.iter();
+
+        let pending_authority_account_info = next_account_info(account_info_iter)?;
+
         msg!("Obtaining Prorgram Data Account");
         let batch0_sale_account_info = next_account_info(account_info_iter)?;
+        assert_writable(batch0_sale_account_info)?;
+        assert_owned_by(batch0_sale_account_info, batch0_sale_program_id)?;
         let mut batch0_sale_account_data =
-            Batch0SaleProgramData::unpack(&batch0_sale_account_info.try_borrow_data()?)?;
+            Self::load_sale(batch0_sale_account_info)?;
 
-        if !batch0_sale_account_data.is_initialized {
-            msg!("Program has not been initilaized");
-            return Err(ProgramError::UninitializedAccount);
+        if batch0_sale_account_data.pending_authority == Pubkey::default() {
+            msg!("No authority transfer has been proposed");
+            return Err(NoPendingAuthority.into());
+        }
+        if *pending_authority_account_info.key != batch0_sale_account_data.pending_authority {
+            msg!("{} is not the proposed authority", pending_authority_account_info.key);
+            return Err(WrongAuthority.into());
+        }
+        if !pending_authority_account_info.is_signer {
+            msg!("Proposed authority must be a signer");
+            return Err(MissingAuthoritySignature.into());
         }
 
-        batch0_sale_account_data.current_price_per_token = new_price_per_token;
+        msg!(
+            "Sale authority {} -> {}",
+            batch0_sale_account_data.authority,
+            batch0_sale_account_data.pending_authority
+        );
+        Batch0SaleEvent::AuthorityChanged {
+            sale: *batch0_sale_account_info.key,
+            old_authority: batch0_sale_account_data.authority,
+            new_authority: batch0_sale_account_data.pending_authority,
+        }
+        .emit();
+        batch0_sale_account_data.authority = batch0_sale_account_data.pending_authority;
+        batch0_sale_account_data.pending_authority = Pubkey::default();
 
         Batch0SaleProgramData::pack(
             batch0_sale_account_data,
@@ -195,137 +806,880 @@ impl Processor {
         return Ok(());
     }
 
+    fn update_price(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        new_price_per_token: u64,
+    ) -> ProgramResult {
+        if new_price_per_token == 0 {
+            msg!("Price per token was 0");
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, _| {
+            sale.current_price_per_token = new_price_per_token;
+            return Ok(());
+        });
+    }
+
+    fn update_tier<F>(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        tier_id: u8,
+        update: F,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&mut PackageTier) -> ProgramResult,
+    {
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, _| {
+            let tier = sale.tiers.get_mut(tier_id as usize).ok_or(InvalidTier)?;
+            return update(tier);
+        });
+    }
+
+    fn set_bonus_schedule(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        bonus_schedule: [BonusBreakpoint; MAX_BONUS_BREAKPOINTS],
+    ) -> ProgramResult {
+        if bonus_schedule
+            .iter()
+            .any(|breakpoint| breakpoint.bonus_bps as u64 > BONUS_BPS_DENOMINATOR)
+        {
+            msg!("Bonus breakpoints are capped at {} bps", BONUS_BPS_DENOMINATOR);
+            return Err(InvalidBonusSchedule.into());
+        }
+
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, _| {
+            sale.bonus_schedule = bonus_schedule;
+            return Ok(());
+        });
+    }
+
+    //authority account info - Must sign, and match the sale's authority
+    //batch0 sale program account info - Holds the payment settings
+    //till account info - New till; a token account of the payment mint when one is passed
+    //payment mint - Optional; omit to take payment in lamports
+    // new_price_per_token - Price in base units of the new currency; a price in
+    // the old currency would be misread after the switch
+    fn set_payment_mint(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        new_price_per_token: u64,
+    ) -> ProgramResult {
+        if new_price_per_token == 0 {
+            msg!("Price per token was 0");
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, remaining_accounts| {
+            let account_info_iter = &mut remaining_accounts.iter();
+            let till_account_info = next_account_info(account_info_iter)?;
+
+            match next_account_info(account_info_iter) {
+                Ok(payment_mint_info) => {
+                    spl_token_2022::check_spl_token_program_account(payment_mint_info.owner)
+                        .map_err(|_| IncorrectPaymentTokenProgram)?;
+                    assert_owned_by(till_account_info, payment_mint_info.owner)?;
+
+                    let till_data = till_account_info.try_borrow_data()?;
+                    let till_account = StateWithExtensions::<Account>::unpack(&till_data)
+                        .map_err(|_| InvalidTokenAccount)?;
+                    if till_account.base.mint != *payment_mint_info.key {
+                        msg!("Till holds a different mint than the payment mint");
+                        return Err(InvalidPaymentMint.into());
+                    }
+
+                    msg!("Sale now priced in {}", payment_mint_info.key);
+                    sale.payment_mint_pubkey = *payment_mint_info.key;
+                }
+                Err(_) => {
+                    msg!("Sale now priced in LAMPORTS");
+                    sale.payment_mint_pubkey = Pubkey::default();
+                }
+            }
+            sale.till_pubkey = *till_account_info.key;
+            sale.current_price_per_token = new_price_per_token;
+
+            return Ok(());
+        });
+    }
+
+    fn set_sale_window(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        start_ts: i64,
+        end_ts: i64,
+    ) -> ProgramResult {
+        Self::check_sale_window(start_ts, end_ts)?;
+
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, _| {
+            sale.start_ts = start_ts;
+            sale.end_ts = end_ts;
+            return Ok(());
+        });
+    }
+
+    // max_tokens_per_wallet - Cap on sold + bonus tokens per buyer, 0 disables it
+    fn set_wallet_cap(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        max_tokens_per_wallet: u64,
+    ) -> ProgramResult {
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, _| {
+            sale.max_tokens_per_wallet = max_tokens_per_wallet;
+            return Ok(());
+        });
+    }
+
+    // merkle_root - Allowlist root, all zeroes ends the presale and opens the public sale
+    fn set_merkle_root(
+        account_info_list: &[AccountInfo],
+        batch0_sale_program_id: &Pubkey,
+        merkle_root: [u8; 32],
+    ) -> ProgramResult {
+        return Self::update_sale(account_info_list, batch0_sale_program_id, |sale, _| {
+            sale.merkle_root = merkle_root;
+            return Ok(());
+        });
+    }
+
+    fn check_sale_window(start_ts: i64, end_ts: i64) -> ProgramResult {
+        if start_ts != 0 && end_ts != 0 && end_ts <= start_ts {
+            msg!("Sale window {} - {} is empty", start_ts, end_ts);
+            return Err(InvalidSaleWindow.into());
+        }
+
+        return Ok(());
+    }
+
+    fn resolve_tier(
+        sale: &Batch0SaleProgramData,
+        tier_id: u8,
+    ) -> Result<(u64, u64, Option<u8>), ProgramError> {
+        let tier = sale.tier(tier_id).ok_or(InvalidTier)?;
+        if !tier.is_enabled {
+            msg!("Package tier {} is disabled", tier_id);
+            return Err(TierDisabled.into());
+        }
+
+        return Ok((tier.sold_tokens, tier.bonus_tokens, Some(tier_id)));
+    }
+
     //buyer account info
     //till account info
     //temp token account info - For transfer the token to Buyer
-    //token sale program account info - For getting data about TokenSaleProgram
+    //token sale program account info - For getting data about TokenSaleProgram, and its running totals
     //system program - For transfer SOL
     //buyer token account info - For the buyer to receive the token
+    //recipient - Wallet the tokens are gifted to (only when is_gift is set)
     //token program - For transfer the token
+    //mint - Mint sold by the sale
+    //associated token program - Creates the buyer token account (only when create_buyer_token_account is set)
     //pda - For signing when send the token from temp token account
-    // number_of_tokens - Amount of tokens user want to buy
-
-    fn buy_tokens(
+    //buyer record - PDA tracking this buyer's totals, created on first purchase
+    //receipt - Uncreated PDA for this purchase's receipt (only when the sale has receipts enabled)
+    //buyer payment token account info - Pays the till (only when the sale has a payment mint)
+    //payment mint - Mint the sale is priced in (only when the sale has a payment mint)
+    //payment token program - Owner of the payment mint (only when the sale has a payment mint)
+    //transfer hook accounts - Remaining accounts: the hook program, its extra-account-metas PDA and the accounts it lists (only when the mint has a TransferHook)
+    // resolve_order - Resolves the (sold, bonus) token amounts, and the package tier if any, against the sale state
+    // max_total_lamports - Most the buyer is willing to pay for the sold tokens
+    // expected_price - Price per token the buyer saw, 0 skips the check
+    // presale_proof - (allocation, merkle proof) of the buyer's allowlist entry
+    // create_buyer_token_account - Create the buyer's associated token account if it is missing
+    // is_gift - Deliver to the recipient's associated token account; the buyer still pays, and the
+    //           purchase counts toward the buyer's record and wallet cap
+    #[allow(clippy::too_many_arguments)]
+    fn buy_tokens<F>(
         accounts: &[AccountInfo],
         token_sale_program_id: &Pubkey,
-        sold_tokens: u64,
-        bonus_tokens: u64,
-    ) -> ProgramResult {
+        resolve_order: F,
+        max_total_lamports: u64,
+        expected_price: u64,
+        presale_proof: Option<(u64, &[[u8; 32]])>,
+        create_buyer_token_account: bool,
+        is_gift: bool,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&Batch0SaleProgramData) -> Result<(u64, u64, Option<u8>), ProgramError>,
+    {
         let account_info_iter = &mut accounts.iter();
 
         let buyer_account_info = next_account_info(account_info_iter)?;
-        if !buyer_account_info.is_signer {
-            msg!("Buyer required to sign for purchase");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        assert_signer(buyer_account_info)?;
+        assert_writable(buyer_account_info)?;
 
         let batch0_sale_account_info = next_account_info(account_info_iter)?;
-        let batch0_sale_account_data =
-            Batch0SaleProgramData::unpack(&batch0_sale_account_info.try_borrow_data()?)?;
+        assert_owned_by(batch0_sale_account_info, token_sale_program_id)?;
+        assert_writable(batch0_sale_account_info)?;
+        let mut batch0_sale_account_data = Self::load_sale(batch0_sale_account_info)?;
 
         if !batch0_sale_account_data.is_initialized {
             msg!("Program has not been initilaized");
             return Err(ProgramError::UninitializedAccount);
         }
 
-        let till_account_info = next_account_info(account_info_iter)?;
-        if *till_account_info.key != batch0_sale_account_data.till_pubkey {
-            msg!("Invalid till account passed");
-            return Err(ProgramError::InvalidAccountData);
+        if batch0_sale_account_data.status == SaleStatus::Paused {
+            msg!("Sale is paused");
+            return Err(SalePaused.into());
         }
 
-        let shelf_account_info = next_account_info(account_info_iter)?;
-        if *shelf_account_info.key != batch0_sale_account_data.shelf_pubkey {
-            msg!("Invalid shelf account passed");
-            return Err(ProgramError::InvalidAccountData);
+        let allocation = match presale_proof {
+            Some((allocation, proof)) => {
+                let leaf = allowlist_leaf(buyer_account_info.key, allocation);
+                if !batch0_sale_account_data.is_presale()
+                    || !verify_proof(proof, &batch0_sale_account_data.merkle_root, leaf)
+                {
+                    msg!("Buyer is not on the presale allowlist");
+                    return Err(InvalidMerkleProof.into());
+                }
+                allocation
+            }
+            None => {
+                if batch0_sale_account_data.is_presale() {
+                    msg!("Sale is in presale, an allowlist proof is required");
+                    return Err(PresaleProofRequired.into());
+                }
+                0
+            }
+        };
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        if batch0_sale_account_data.start_ts != 0 && now < batch0_sale_account_data.start_ts {
+            msg!("Sale opens at {}", batch0_sale_account_data.start_ts);
+            return Err(SaleNotStarted.into());
+        }
+        if batch0_sale_account_data.end_ts != 0 && now >= batch0_sale_account_data.end_ts {
+            msg!("Sale closed at {}", batch0_sale_account_data.end_ts);
+            return Err(SaleEnded.into());
         }
 
-        let purchase_lamports = sold_tokens * batch0_sale_account_data.current_price_per_token;
-        msg!("Purchsing {} tokens for {} LAMPORTS", sold_tokens, purchase_lamports);
+        let (sold_tokens, bonus_tokens, tier_id) = resolve_order(&batch0_sale_account_data)?;
 
-        msg!(
-            "Transfer {} SOL : buy account -> seller account",
-            purchase_lamports
-        );
-        let transfer_sol_to_seller = system_instruction::transfer(
-            buyer_account_info.key,
+        let till_account_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(
             till_account_info.key,
-            purchase_lamports,
-        );
+            &batch0_sale_account_data.till_pubkey,
+            InvalidTillAccount,
+        )?;
+        assert_writable(till_account_info)?;
 
-        let system_program = next_account_info(account_info_iter)?;
-        invoke(
-            &transfer_sol_to_seller,
-            &[
-                buyer_account_info.clone(),
-                till_account_info.clone(),
-                system_program.clone(),
-            ],
+        let shelf_account_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(
+            shelf_account_info.key,
+            &batch0_sale_account_data.shelf_pubkey,
+            InvalidShelfAccount,
         )?;
+        assert_writable(shelf_account_info)?;
+
+        if expected_price != 0 && batch0_sale_account_data.current_price_per_token != expected_price
+        {
+            msg!(
+                "Price is {} per token, buyer expected {}",
+                batch0_sale_account_data.current_price_per_token,
+                expected_price
+            );
+            return Err(PriceChanged.into());
+        }
+
+        let system_program = next_account_info(account_info_iter)?;
+        assert_system_program(system_program)?;
 
-        msg!("transfer Token : shelf account -> buyer token account");
         let buyer_token_account_info = next_account_info(account_info_iter)?;
+        assert_writable(buyer_token_account_info)?;
+
+        let recipient_account_info = if is_gift {
+            next_account_info(account_info_iter)?
+        } else {
+            buyer_account_info
+        };
+
         let token_program = next_account_info(account_info_iter)?;
+        assert_token_2022_program(token_program)?;
+
         let token_mint_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(
+            token_mint_info.key,
+            &batch0_sale_account_data.mint_pubkey,
+            MintMismatch,
+        )?;
+
+        if is_gift {
+            let recipient_token_account = get_associated_token_address_with_program_id(
+                recipient_account_info.key,
+                token_mint_info.key,
+                token_program.key,
+            );
+            assert_keys_equal(
+                buyer_token_account_info.key,
+                &recipient_token_account,
+                InvalidRecipientTokenAccount,
+            )?;
+            msg!("Gifting to {}", recipient_account_info.key);
+        }
+
+        if create_buyer_token_account {
+            let associated_token_program = next_account_info(account_info_iter)?;
+            assert_associated_token_program(associated_token_program)?;
+
+            //idempotent, so an existing buyer token account is left as it is
+            msg!("create buyer token account : {}", buyer_token_account_info.key);
+            let create_buyer_token_account_ix = create_associated_token_account_idempotent(
+                buyer_account_info.key,
+                recipient_account_info.key,
+                token_mint_info.key,
+                token_program.key,
+            );
+            invoke(
+                &create_buyer_token_account_ix,
+                &[
+                    buyer_account_info.clone(),
+                    buyer_token_account_info.clone(),
+                    recipient_account_info.clone(),
+                    token_mint_info.clone(),
+                    system_program.clone(),
+                    token_program.clone(),
+                    associated_token_program.clone(),
+                ],
+            )?;
+        }
+
+        let decimals = batch0_sale_account_data.mint_decimals;
+        assert_token_account(shelf_account_info, token_mint_info.key)?;
+        assert_token_account(buyer_token_account_info, token_mint_info.key)?;
+
+        let bump_seed = batch0_sale_account_data.authority_bump;
+        let pda = create_sale_authority_address(
+            batch0_sale_account_info.key,
+            bump_seed,
+            token_sale_program_id,
+        )?;
         let pda_account_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(pda_account_info.key, &pda, InvalidSaleAuthority)?;
 
-        // fetch the number of decimals
-        // let token_mint = spl_token_2022::state::Mint::unpack(&token_mint_info.data.borrow())?;
-        // let decimals = token_mint.decimals;
-        // Step 1: Borrow the account data and hold the reference
-        let mint_data = token_mint_info.try_borrow_data()?;
+        let buyer_record_account_info = next_account_info(account_info_iter)?;
+        assert_writable(buyer_record_account_info)?;
 
-        // Step 2: Unpack using StateWithExtensions
-        let mint_with_extensions = StateWithExtensions::<Mint>::unpack(&mint_data)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let delivered_tokens = sold_tokens
+            .checked_add(bonus_tokens)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let transfer_fee_config = Self::transfer_fee_config(token_mint_info)?;
+        let (shelf_amount, transfer_fee) = Self::shelf_transfer_amount(
+            transfer_fee_config.as_ref(),
+            delivered_tokens,
+            clock.epoch,
+        )?;
 
-        let mint = mint_with_extensions.base;
-        let decimals = mint.decimals;
+        //ChargeBuyer bills the fee tokens on top of the sold ones
+        let charged_tokens = match batch0_sale_account_data.transfer_fee_mode {
+            TransferFeeMode::GrossUp => sold_tokens,
+            TransferFeeMode::ChargeBuyer => sold_tokens
+                .checked_add(transfer_fee)
+                .ok_or(ProgramError::ArithmeticOverflow)?,
+        };
+        let purchase_lamports = charged_tokens
+            .checked_mul(batch0_sale_account_data.current_price_per_token)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        msg!("Purchsing {} tokens for {} LAMPORTS", sold_tokens, purchase_lamports);
+        if purchase_lamports > max_total_lamports {
+            msg!("Purchase exceeds buyer's maximum of {} LAMPORTS", max_total_lamports);
+            return Err(PriceChanged.into());
+        }
 
-        let (pda, bump_seed) =
-            Pubkey::find_program_address(&[b"batch0_sale"], token_sale_program_id);
+        let purchase_index = Self::record_purchase(
+            token_sale_program_id,
+            batch0_sale_account_info,
+            buyer_account_info,
+            buyer_record_account_info,
+            system_program,
+            &batch0_sale_account_data,
+            delivered_tokens,
+            purchase_lamports,
+            allocation,
+        )?;
 
-        let transfer_token_to_buyer_ix = spl_token_2022::instruction::transfer_checked(
-            token_program.key,
-            shelf_account_info.key,
-            token_mint_info.key,
-            buyer_token_account_info.key,
-            &pda,
-            &[&pda],
-            sold_tokens + bonus_tokens,
-            decimals,
-        )
+        let receipt = PurchaseReceipt {
+            is_initialized: true,
+            sale_pubkey: *batch0_sale_account_info.key,
+            buyer_pubkey: *buyer_account_info.key,
+            purchase_index,
+            sold_tokens,
+            bonus_tokens,
+            amount_paid: purchase_lamports,
+            price_per_token: batch0_sale_account_data.current_price_per_token,
+            slot: clock.slot,
+            transfer_fee,
+            recipient_pubkey: *recipient_account_info.key,
+        };
+        if batch0_sale_account_data.receipts_enabled {
+            let receipt_account_info = next_account_info(account_info_iter)?;
+            Self::write_receipt(
+                token_sale_program_id,
+                buyer_account_info,
+                receipt_account_info,
+                system_program,
+                &receipt,
+            )?;
+        }
+
+        if batch0_sale_account_data.payment_mint_pubkey == Pubkey::default() {
+            msg!(
+                "Transfer {} SOL : buy account -> seller account",
+                purchase_lamports
+            );
+            let transfer_sol_to_seller = system_instruction::transfer(
+                buyer_account_info.key,
+                till_account_info.key,
+                purchase_lamports,
+            );
+
+            invoke(
+                &transfer_sol_to_seller,
+                &[
+                    buyer_account_info.clone(),
+                    till_account_info.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        } else {
+            let buyer_payment_account_info = next_account_info(account_info_iter)?;
+            assert_writable(buyer_payment_account_info)?;
+
+            let payment_mint_info = next_account_info(account_info_iter)?;
+            assert_keys_equal(
+                payment_mint_info.key,
+                &batch0_sale_account_data.payment_mint_pubkey,
+                InvalidPaymentMint,
+            )?;
+
+            let payment_token_program = next_account_info(account_info_iter)?;
+            spl_token_2022::check_spl_token_program_account(payment_token_program.key)
+                .map_err(|_| IncorrectPaymentTokenProgram)?;
+            assert_owned_by(payment_mint_info, payment_token_program.key)?;
+
+            let payment_mint_data = payment_mint_info.try_borrow_data()?;
+            let payment_decimals = StateWithExtensions::<Mint>::unpack(&payment_mint_data)
+                .map_err(|_| InvalidMintAccount)?
+                .base
+                .decimals;
+            drop(payment_mint_data);
+
+            msg!(
+                "Transfer {} payment tokens : buyer payment account -> till account",
+                purchase_lamports
+            );
+            let transfer_payment_to_till_ix = spl_token_2022::instruction::transfer_checked(
+                payment_token_program.key,
+                buyer_payment_account_info.key,
+                payment_mint_info.key,
+                till_account_info.key,
+                buyer_account_info.key,
+                &[],
+                purchase_lamports,
+                payment_decimals,
+            )?;
+
+            invoke(
+                &transfer_payment_to_till_ix,
+                &[
+                    buyer_payment_account_info.clone(),
+                    payment_mint_info.clone(),
+                    till_account_info.clone(),
+                    buyer_account_info.clone(),
+                    payment_token_program.clone(),
+                ],
+            )?;
+        }
+
+        msg!("transfer Token : shelf account -> buyer token account");
+        let transfer_token_to_buyer_ix = if transfer_fee_config.is_some() {
+            msg!("Sending {} tokens, {} withheld as transfer fee", shelf_amount, transfer_fee);
+            transfer_fee_instruction::transfer_checked_with_fee(
+                token_program.key,
+                shelf_account_info.key,
+                token_mint_info.key,
+                buyer_token_account_info.key,
+                &pda,
+                &[&pda],
+                shelf_amount,
+                decimals,
+                transfer_fee,
+            )
+        } else {
+            spl_token_2022::instruction::transfer_checked(
+                token_program.key,
+                shelf_account_info.key,
+                token_mint_info.key,
+                buyer_token_account_info.key,
+                &pda,
+                &[&pda],
+                shelf_amount,
+                decimals,
+            )
+        }
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
-        invoke_signed(
-            &transfer_token_to_buyer_ix,
-            &[
+        //whatever is left are the mint's transfer hook accounts, if it has one
+        Self::invoke_shelf_transfer(
+            transfer_token_to_buyer_ix,
+            vec![
                 shelf_account_info.clone(),       // [writable] Source
                 token_mint_info.clone(),          // [readable] Mint
                 buyer_token_account_info.clone(), // [writable] Destination
-                pda_account_info.clone(),  
-                token_program.clone()
+                pda_account_info.clone(),
+                token_program.clone(),
+            ],
+            shelf_amount,
+            account_info_iter.as_slice(),
+            &[
+                SALE_AUTHORITY_SEED,
+                batch0_sale_account_info.key.as_ref(),
+                &[bump_seed],
             ],
-            &[&[&b"batch0_sale"[..], &[bump_seed]]],
         )?;
 
+        batch0_sale_account_data.record_purchase(
+            tier_id,
+            sold_tokens,
+            bonus_tokens,
+            purchase_lamports,
+        )?;
+        Batch0SaleProgramData::pack(
+            batch0_sale_account_data,
+            &mut batch0_sale_account_info.try_borrow_mut_data()?,
+        )?;
+
+        Batch0SaleEvent::Purchased {
+            sale: receipt.sale_pubkey,
+            buyer: receipt.buyer_pubkey,
+            tier_id,
+            sold_tokens,
+            bonus_tokens,
+            amount_paid: receipt.amount_paid,
+            price_per_token: receipt.price_per_token,
+            purchase_index,
+            transfer_fee,
+            recipient: receipt.recipient_pubkey,
+        }
+        .emit();
+
+        let mut receipt_data = [0u8; PurchaseReceipt::LEN];
+        receipt.pack_into_slice(&mut receipt_data);
+        set_return_data(&receipt_data);
+
         return Ok(());
     }
 
+    //the mint's TransferFeeConfig, if it has that extension
+    fn transfer_fee_config(
+        mint_info: &AccountInfo,
+    ) -> Result<Option<TransferFeeConfig>, ProgramError> {
+        let mint_data = mint_info.try_borrow_data()?;
+        let mint =
+            StateWithExtensions::<Mint>::unpack(&mint_data).map_err(|_| InvalidMintAccount)?;
+
+        return Ok(mint.get_extension::<TransferFeeConfig>().ok().copied());
+    }
+
+    //(amount sent from the shelf, fee the mint withholds from it) for a
+    //purchase delivering `delivered_tokens`; the fee is added on top so the
+    //buyer nets the full package
+    fn shelf_transfer_amount(
+        transfer_fee_config: Option<&TransferFeeConfig>,
+        delivered_tokens: u64,
+        epoch: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        let transfer_fee_config = match transfer_fee_config {
+            Some(transfer_fee_config) => transfer_fee_config,
+            None => return Ok((delivered_tokens, 0)),
+        };
+
+        let shelf_amount = transfer_fee_config
+            .calculate_inverse_epoch_fee(epoch, delivered_tokens)
+            .and_then(|fee| delivered_tokens.checked_add(fee))
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let transfer_fee = transfer_fee_config
+            .calculate_epoch_fee(epoch, shelf_amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        return Ok((shelf_amount, transfer_fee));
+    }
+
+    //signs a shelf transfer as the sale authority. When the mint has a
+    //TransferHook, the hook program, its extra-account-metas PDA and the
+    //accounts that PDA lists are picked out of `transfer_hook_accounts` and
+    //appended to the transfer, in the order Token-2022 hands them to the hook
+    fn invoke_shelf_transfer<'a>(
+        mut transfer_ix: Instruction,
+        mut account_infos: Vec<AccountInfo<'a>>,
+        amount: u64,
+        transfer_hook_accounts: &[AccountInfo<'a>],
+        sale_authority_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        //transfer_checked reads source, mint, destination and authority first
+        let find_account_info = |index: usize| -> Result<AccountInfo<'a>, ProgramError> {
+            let key = transfer_ix.accounts[index].pubkey;
+            return account_infos
+                .iter()
+                .find(|account_info| *account_info.key == key)
+                .cloned()
+                .ok_or(ProgramError::NotEnoughAccountKeys);
+        };
+        let source_info = find_account_info(0)?;
+        let mint_info = find_account_info(1)?;
+        let destination_info = find_account_info(2)?;
+        let authority_info = find_account_info(3)?;
+
+        let transfer_hook_program_id = {
+            let mint_data = mint_info.try_borrow_data()?;
+            let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+            transfer_hook::get_program_id(&mint)
+        };
+
+        if let Some(transfer_hook_program_id) = transfer_hook_program_id {
+            add_extra_accounts_for_execute_cpi(
+                &mut transfer_ix,
+                &mut account_infos,
+                &transfer_hook_program_id,
+                source_info,
+                mint_info,
+                destination_info,
+                authority_info,
+                amount,
+                transfer_hook_accounts,
+            )?;
+        }
+
+        return invoke_signed(&transfer_ix, &account_infos, &[sale_authority_seeds]);
+    }
+
+    //creates a program-owned PDA funded by the payer. CreateAccount fails once
+    //the address holds any lamports, and anyone can send it some, so a
+    //pre-funded PDA is topped up to rent exemption and allocated and assigned
+    //in place instead
+    fn create_pda_account<'a>(
+        program_id: &Pubkey,
+        payer_account_info: &AccountInfo<'a>,
+        new_account_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        space: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let rent = Rent::get()?;
+        let required_lamports = rent.minimum_balance(space);
+
+        if new_account_info.lamports() == 0 {
+            return invoke_signed(
+                &system_instruction::create_account(
+                    payer_account_info.key,
+                    new_account_info.key,
+                    required_lamports,
+                    space as u64,
+                    program_id,
+                ),
+                &[
+                    payer_account_info.clone(),
+                    new_account_info.clone(),
+                    system_program.clone(),
+                ],
+                &[signer_seeds],
+            );
+        }
+
+        let top_up_lamports = required_lamports.saturating_sub(new_account_info.lamports());
+        if top_up_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_account_info.key,
+                    new_account_info.key,
+                    top_up_lamports,
+                ),
+                &[
+                    payer_account_info.clone(),
+                    new_account_info.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_account_info.key, space as u64),
+            &[new_account_info.clone(), system_program.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account_info.key, program_id),
+            &[new_account_info.clone(), system_program.clone()],
+            &[signer_seeds],
+        )?;
+
+        return Ok(());
+    }
+
+    //creates the receipt PDA for this purchase; the buyer pays its rent
+    fn write_receipt<'a>(
+        program_id: &Pubkey,
+        buyer_account_info: &AccountInfo<'a>,
+        receipt_account_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        receipt: &PurchaseReceipt,
+    ) -> ProgramResult {
+        assert_writable(receipt_account_info)?;
+
+        let (receipt_pubkey, bump_seed) = find_receipt_address(
+            &receipt.sale_pubkey,
+            &receipt.buyer_pubkey,
+            receipt.purchase_index,
+            program_id,
+        );
+        assert_keys_equal(receipt_account_info.key, &receipt_pubkey, InvalidReceiptAccount)?;
+
+        msg!("Creating receipt {}", receipt_pubkey);
+        Self::create_pda_account(
+            program_id,
+            buyer_account_info,
+            receipt_account_info,
+            system_program,
+            PurchaseReceipt::LEN,
+            &[
+                RECEIPT_SEED,
+                receipt.sale_pubkey.as_ref(),
+                receipt.buyer_pubkey.as_ref(),
+                &receipt.purchase_index.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        receipt.pack_into_slice(&mut receipt_account_info.try_borrow_mut_data()?);
+
+        return Ok(());
+    }
+
+    //creates the buyer's record on first purchase, then adds this purchase to
+    //its running totals and enforces the sale's per-wallet cap and any presale
+    //allocation (0 for none)
+    #[allow(clippy::too_many_arguments)]
+    fn record_purchase<'a>(
+        program_id: &Pubkey,
+        sale_account_info: &AccountInfo<'a>,
+        buyer_account_info: &AccountInfo<'a>,
+        buyer_record_account_info: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        sale: &Batch0SaleProgramData,
+        delivered_tokens: u64,
+        paid: u64,
+        allocation: u64,
+    ) -> Result<u64, ProgramError> {
+        let mut buyer_record = if buyer_record_account_info.data_is_empty() {
+            let (buyer_record_pubkey, bump_seed) =
+                find_buyer_record_address(sale_account_info.key, buyer_account_info.key, program_id);
+            if buyer_record_pubkey != *buyer_record_account_info.key {
+                msg!("Invalid buyer record account passed");
+                return Err(InvalidBuyerRecord.into());
+            }
+
+            msg!("Creating buyer record {}", buyer_record_pubkey);
+            Self::create_pda_account(
+                program_id,
+                buyer_account_info,
+                buyer_record_account_info,
+                system_program,
+                BuyerRecord::LEN,
+                &[
+                    BUYER_RECORD_SEED,
+                    sale_account_info.key.as_ref(),
+                    buyer_account_info.key.as_ref(),
+                    &[bump_seed],
+                ],
+            )?;
+
+            BuyerRecord {
+                is_initialized: true,
+                sale_pubkey: *sale_account_info.key,
+                buyer_pubkey: *buyer_account_info.key,
+                tokens_purchased: 0,
+                total_paid: 0,
+                bump_seed,
+                purchase_count: 0,
+            }
+        } else {
+            if buyer_record_account_info.owner != program_id {
+                msg!("Buyer record is not owned by the sale program");
+                return Err(InvalidBuyerRecord.into());
+            }
+            let buyer_record =
+                BuyerRecord::unpack(&buyer_record_account_info.try_borrow_data()?)?;
+            if buyer_record.sale_pubkey != *sale_account_info.key
+                || buyer_record.buyer_pubkey != *buyer_account_info.key
+            {
+                msg!("Buyer record belongs to another sale or buyer");
+                return Err(InvalidBuyerRecord.into());
+            }
+            buyer_record
+        };
+
+        buyer_record.tokens_purchased = buyer_record
+            .tokens_purchased
+            .checked_add(delivered_tokens)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        buyer_record.total_paid = buyer_record
+            .total_paid
+            .checked_add(paid)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if sale.max_tokens_per_wallet != 0
+            && buyer_record.tokens_purchased > sale.max_tokens_per_wallet
+        {
+            msg!(
+                "Wallet would hold {} tokens from this sale, cap is {}",
+                buyer_record.tokens_purchased,
+                sale.max_tokens_per_wallet
+            );
+            return Err(WalletCapExceeded.into());
+        }
+
+        if allocation != 0 && buyer_record.tokens_purchased > allocation {
+            msg!(
+                "Wallet would hold {} tokens from this sale, allocation is {}",
+                buyer_record.tokens_purchased,
+                allocation
+            );
+            return Err(AllocationExceeded.into());
+        }
+
+        let purchase_index = buyer_record.purchase_count;
+        buyer_record.purchase_count = purchase_index
+            .checked_add(1)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        BuyerRecord::pack(
+            buyer_record,
+            &mut buyer_record_account_info.try_borrow_mut_data()?,
+        )?;
+
+        return Ok(purchase_index);
+    }
+
+    //authority account info - Must sign, and match the sale's authority
     //inventory_token_account_info - To receive remainining token inventory
     //shelf_account_info - To send For retrieve remain token
     //token_2022_account_info - For transfer the token
     //pda - For signing when send the token from temp token account and close temp token account
     //batch0 sale program account info - To close token sale program
+    //transfer hook accounts - Remaining accounts: the hook program, its extra-account-metas PDA and the accounts it lists (only when the mint has a TransferHook)
     fn end_token_sale(accounts: &[AccountInfo], batch0_sale_program_id: &Pubkey) -> ProgramResult {
         msg!("Ending the Sale");
         let account_info_iter = &mut accounts.iter();
+        let authority_account_info = next_account_info(account_info_iter)?;
         let program_data_account_info = next_account_info(account_info_iter)?;
+        assert_writable(program_data_account_info)?;
+        assert_owned_by(program_data_account_info, batch0_sale_program_id)?;
 
         msg!("Attempting to get the state data");
         // get state data
         let batch0_sale_program_account_data =
-            Batch0SaleProgramData::unpack_unchecked(&program_data_account_info.try_borrow_data()?)?;
+            Self::load_sale(program_data_account_info)?;
 
         msg!(
             "Is Initialized {}",
@@ -336,42 +1690,54 @@ impl Processor {
             return Err(ProgramError::UninitializedAccount);
         }
 
+        Self::check_authority(authority_account_info, &batch0_sale_program_account_data)?;
+
         msg!("Verify inventory account passed");
         let inventory_account_info = next_account_info(account_info_iter)?;
-        if *inventory_account_info.key != batch0_sale_program_account_data.inventory_pubkey {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        assert_keys_equal(
+            inventory_account_info.key,
+            &batch0_sale_program_account_data.inventory_pubkey,
+            InvalidInventoryAccount,
+        )?;
+        assert_writable(inventory_account_info)?;
 
         msg!("Verify shelf account passed");
         let shelf_account_info = next_account_info(account_info_iter)?;
-        if *shelf_account_info.key != batch0_sale_program_account_data.shelf_pubkey {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        assert_keys_equal(
+            shelf_account_info.key,
+            &batch0_sale_program_account_data.shelf_pubkey,
+            InvalidShelfAccount,
+        )?;
+        assert_writable(shelf_account_info)?;
 
-        let (pda, bump) = Pubkey::find_program_address(&[b"batch0_sale"], batch0_sale_program_id);
+        let bump = batch0_sale_program_account_data.authority_bump;
+        let pda = create_sale_authority_address(
+            program_data_account_info.key,
+            bump,
+            batch0_sale_program_id,
+        )?;
 
-        msg!("shelf_account_info.key: {}", shelf_account_info.key);
-        msg!("shelf_account_info.owner: {}", shelf_account_info.owner);
-        msg!("expected owner: {}", spl_token_2022::id());
+        msg!("getting mint data for transfer");
+        let token_mint_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(
+            token_mint_info.key,
+            &batch0_sale_program_account_data.mint_pubkey,
+            MintMismatch,
+        )?;
+        let decimals = batch0_sale_program_account_data.mint_decimals;
+        let shelf_amount = assert_token_account(shelf_account_info, token_mint_info.key)?;
 
         msg!("transfer Token : shelf account -> inventory account");
-        let shelf_account_binding = shelf_account_info.data.borrow();
-        let account_with_extensions =
-            StateWithExtensions::<Account>::unpack(&shelf_account_binding)
-                .map_err(|_| ProgramError::InvalidAccountData)?;
-
-        let shelf_account_info_data = &account_with_extensions.base;
+        let inventory_ata_info = next_account_info(account_info_iter)?;
+        assert_writable(inventory_ata_info)?;
+        assert_token_account(inventory_ata_info, token_mint_info.key)?;
 
-        msg!("getting mint data for transfer");
-        let token_mint_info = next_account_info(account_info_iter)?;
-        let mint_data = token_mint_info.try_borrow_data()?;
-        let mint_with_extensions = StateWithExtensions::<Mint>::unpack(&mint_data)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let token_2022_account_info = next_account_info(account_info_iter)?;
+        assert_token_2022_program(token_2022_account_info)?;
 
-        let mint = mint_with_extensions.base;
-        let decimals = mint.decimals;
+        let pda_account_info = next_account_info(account_info_iter)?;
+        assert_keys_equal(pda_account_info.key, &pda, InvalidSaleAuthority)?;
 
-        let inventory_ata_info = next_account_info(account_info_iter)?;
         let return_to_owner_ix = spl_token_2022::instruction::transfer_checked(
             &spl_token_2022::ID,
             shelf_account_info.key,
@@ -379,26 +1745,36 @@ impl Processor {
             inventory_ata_info.key,
             &pda,
             &[&pda],
-            shelf_account_info_data.amount,
+            shelf_amount,
             decimals,
         )
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
-        drop(shelf_account_binding);
-        let token_2022_account_info = next_account_info(account_info_iter)?;
-        let pda_account_info = next_account_info(account_info_iter)?;
-        invoke_signed(
-            &return_to_owner_ix,
-            &[
+        //whatever is left are the mint's transfer hook accounts, if it has one
+        Self::invoke_shelf_transfer(
+            return_to_owner_ix,
+            vec![
                 token_2022_account_info.clone(),
                 shelf_account_info.clone(),
                 token_mint_info.clone(),
                 inventory_ata_info.clone(),
                 pda_account_info.clone(),
             ],
-            &[&[&b"batch0_sale"[..], &[bump]]],
+            shelf_amount,
+            account_info_iter.as_slice(),
+            &[
+                SALE_AUTHORITY_SEED,
+                program_data_account_info.key.as_ref(),
+                &[bump],
+            ],
         )?;
 
+        Batch0SaleEvent::SaleEnded {
+            sale: *program_data_account_info.key,
+            unsold_tokens: shelf_amount,
+        }
+        .emit();
+
         msg!("close token sale program");
         **inventory_account_info.try_borrow_mut_lamports()? = inventory_account_info
             .lamports()
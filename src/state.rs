@@ -4,30 +4,296 @@ use solana_program::{
     pubkey::Pubkey,
 };
 
+use std::convert::TryInto;
+
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
+//layout version written as the first byte of every sale state account. The
+//original 105-byte layout predates the version byte, but starts with
+//is_initialized, so every live v1 sale reads as version 1
+pub const STATE_VERSION: u8 = 2;
+pub const STATE_V1: u8 = 1;
+pub const STATE_V1_LEN: usize = 105;
+//zeroed tail of the v2 layout. Any change to the layout bumps STATE_VERSION
+//and teaches MigrateState the old one, unless the new field fits in these
+//bytes and reads zero as its default
+pub const STATE_RESERVED_LEN: usize = 128;
+
+pub const MAX_TIERS: usize = 8;
+pub const TIER_NAME_LEN: usize = 16;
+pub const MAX_BONUS_BREAKPOINTS: usize = 8;
+pub const BONUS_BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct PackageTier {
+    pub is_initialized: bool,
+    pub is_enabled: bool,
+    pub name: [u8; TIER_NAME_LEN],
+    pub sold_tokens: u64,
+    pub bonus_tokens: u64,
+    pub purchase_count: u64,
+}
+
+impl PackageTier {
+    pub fn new(name: &str, sold_tokens: u64, bonus_tokens: u64) -> Self {
+        let mut name_bytes = [0u8; TIER_NAME_LEN];
+        let len = name.len().min(TIER_NAME_LEN);
+        name_bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        return PackageTier {
+            is_initialized: true,
+            is_enabled: true,
+            name: name_bytes,
+            sold_tokens,
+            bonus_tokens,
+            purchase_count: 0,
+        };
+    }
+
+    //the original Shot..Barrel packages, seeded into every new sale
+    pub fn default_tiers() -> [PackageTier; MAX_TIERS] {
+        let mut tiers = [PackageTier::default(); MAX_TIERS];
+        tiers[0] = PackageTier::new("Shot", 1000, 0);
+        tiers[1] = PackageTier::new("Double", 2000, 100);
+        tiers[2] = PackageTier::new("Flask", 5000, 500);
+        tiers[3] = PackageTier::new("Fifth", 25000, 5000);
+        tiers[4] = PackageTier::new("Case", 300000, 70000);
+        tiers[5] = PackageTier::new("Barrel", 1500000, 375000);
+        return tiers;
+    }
+}
+
+impl Sealed for PackageTier {}
+
+impl IsInitialized for PackageTier {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PackageTier {
+    const LEN: usize = 42; // 1 + 1 + 16 + 8 + 8 + 8
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PackageTier::LEN];
+        let (is_initialized, is_enabled, name, sold_tokens, bonus_tokens, purchase_count) =
+            array_refs![src, 1, 1, TIER_NAME_LEN, 8, 8, 8];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let is_enabled = match is_enabled {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        return Ok(PackageTier {
+            is_initialized,
+            is_enabled,
+            name: *name,
+            sold_tokens: u64::from_le_bytes(*sold_tokens),
+            bonus_tokens: u64::from_le_bytes(*bonus_tokens),
+            purchase_count: u64::from_le_bytes(*purchase_count),
+        });
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PackageTier::LEN];
+        let (
+            is_initialized_dst,
+            is_enabled_dst,
+            name_dst,
+            sold_tokens_dst,
+            bonus_tokens_dst,
+            purchase_count_dst,
+        ) = mut_array_refs![dst, 1, 1, TIER_NAME_LEN, 8, 8, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        is_enabled_dst[0] = self.is_enabled as u8;
+        name_dst.copy_from_slice(&self.name);
+        sold_tokens_dst.copy_from_slice(&self.sold_tokens.to_le_bytes());
+        bonus_tokens_dst.copy_from_slice(&self.bonus_tokens.to_le_bytes());
+        purchase_count_dst.copy_from_slice(&self.purchase_count.to_le_bytes());
+    }
+}
+
+//a BuyTokens purchase of at least `min_tokens` earns `bonus_bps` basis points on top
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BonusBreakpoint {
+    pub min_tokens: u64,
+    pub bonus_bps: u16,
+}
+
+impl BonusBreakpoint {
+    //mirrors the bonus ratios of the default package tiers
+    pub fn default_schedule() -> [BonusBreakpoint; MAX_BONUS_BREAKPOINTS] {
+        let mut schedule = [BonusBreakpoint::default(); MAX_BONUS_BREAKPOINTS];
+        schedule[0] = BonusBreakpoint { min_tokens: 2000, bonus_bps: 500 };
+        schedule[1] = BonusBreakpoint { min_tokens: 5000, bonus_bps: 1000 };
+        schedule[2] = BonusBreakpoint { min_tokens: 25000, bonus_bps: 2000 };
+        schedule[3] = BonusBreakpoint { min_tokens: 300000, bonus_bps: 2330 };
+        schedule[4] = BonusBreakpoint { min_tokens: 1500000, bonus_bps: 2500 };
+        return schedule;
+    }
+}
+
+impl Sealed for BonusBreakpoint {}
+
+impl Pack for BonusBreakpoint {
+    const LEN: usize = 10; // 8 + 2
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, BonusBreakpoint::LEN];
+        let (min_tokens, bonus_bps) = array_refs![src, 8, 2];
+
+        return Ok(BonusBreakpoint {
+            min_tokens: u64::from_le_bytes(*min_tokens),
+            bonus_bps: u16::from_le_bytes(*bonus_bps),
+        });
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, BonusBreakpoint::LEN];
+        let (min_tokens_dst, bonus_bps_dst) = mut_array_refs![dst, 8, 2];
+
+        min_tokens_dst.copy_from_slice(&self.min_tokens.to_le_bytes());
+        bonus_bps_dst.copy_from_slice(&self.bonus_bps.to_le_bytes());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaleStatus {
+    Active = 0,
+    Paused = 1,
+}
+
+impl SaleStatus {
+    pub fn from_u8(status: u8) -> Option<Self> {
+        return match status {
+            0 => Some(SaleStatus::Active),
+            1 => Some(SaleStatus::Paused),
+            _ => None,
+        };
+    }
+}
+
+//who bears a Token-2022 transfer fee on the shelf -> buyer transfer. Either
+//way the shelf sends the package plus the fee, so the buyer nets the package
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferFeeMode {
+    //the sale absorbs the fee tokens
+    GrossUp = 0,
+    //the buyer also pays for the fee tokens at the token price
+    ChargeBuyer = 1,
+}
+
+impl TransferFeeMode {
+    pub fn from_u8(mode: u8) -> Option<Self> {
+        return match mode {
+            0 => Some(TransferFeeMode::GrossUp),
+            1 => Some(TransferFeeMode::ChargeBuyer),
+            _ => None,
+        };
+    }
+}
+
 pub struct Batch0SaleProgramData {
+    pub version: u8,
     pub is_initialized: bool,
     pub current_price_per_token: u64,
     pub inventory_pubkey: Pubkey,
     pub shelf_pubkey: Pubkey,
-    pub till_pubkey: Pubkey
+    pub till_pubkey: Pubkey,
+    pub mint_pubkey: Pubkey,
+    pub mint_decimals: u8,
+    pub sale_id: u64,
+    pub state_bump: u8,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub authority_bump: u8,
+    pub payment_mint_pubkey: Pubkey,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub max_tokens_per_wallet: u64,
+    pub merkle_root: [u8; 32],
+    pub status: SaleStatus,
+    pub receipts_enabled: bool,
+    pub transfer_fee_mode: TransferFeeMode,
+    pub total_tokens_sold: u64,
+    pub total_bonus_issued: u64,
+    pub total_collected: u64, // lamports, or payment mint base units
+    pub total_purchases: u64,
+    pub tiers: [PackageTier; MAX_TIERS],
+    pub bonus_schedule: [BonusBreakpoint; MAX_BONUS_BREAKPOINTS],
 }
 
 impl Batch0SaleProgramData {
-    pub fn init(
+    pub fn tier(&self, tier_id: u8) -> Option<&PackageTier> {
+        self.tiers
+            .get(tier_id as usize)
+            .filter(|tier| tier.is_initialized)
+    }
+
+    //a non-zero merkle root means the sale is in its allowlisted presale phase
+    pub fn is_presale(&self) -> bool {
+        self.merkle_root != [0u8; 32]
+    }
+
+    //bonus for an arbitrary-quantity purchase, from the best breakpoint reached
+    pub fn bonus_for(&self, amount: u64) -> Option<u64> {
+        let bonus_bps = self
+            .bonus_schedule
+            .iter()
+            .filter(|breakpoint| amount >= breakpoint.min_tokens)
+            .map(|breakpoint| breakpoint.bonus_bps)
+            .max()
+            .unwrap_or(0);
+
+        let bonus = (amount as u128)
+            .checked_mul(bonus_bps as u128)?
+            .checked_div(BONUS_BPS_DENOMINATOR as u128)?;
+        return bonus.try_into().ok();
+    }
+
+    //adds one purchase to the running sale totals, and to the tier's purchase
+    //count when it was a package purchase
+    pub fn record_purchase(
         &mut self,
-        is_initialized: bool,              // 1
-        current_price_per_token: u64, // 64
-        inventory_pubkey: Pubkey,              // 32
-        shelf_pubkey: Pubkey, // 32
-        till_pubkey: Pubkey // 32
-    ) {
-        self.is_initialized = is_initialized;
-        self.current_price_per_token = current_price_per_token;
-        self.inventory_pubkey = inventory_pubkey;
-        self.shelf_pubkey = shelf_pubkey;
-        self.till_pubkey = till_pubkey;
+        tier_id: Option<u8>,
+        sold_tokens: u64,
+        bonus_tokens: u64,
+        paid: u64,
+    ) -> Result<(), ProgramError> {
+        self.total_tokens_sold = self
+            .total_tokens_sold
+            .checked_add(sold_tokens)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.total_bonus_issued = self
+            .total_bonus_issued
+            .checked_add(bonus_tokens)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.total_collected = self
+            .total_collected
+            .checked_add(paid)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.total_purchases = self
+            .total_purchases
+            .checked_add(1)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if let Some(tier_id) = tier_id {
+            let tier = self
+                .tiers
+                .get_mut(tier_id as usize)
+                .ok_or(ProgramError::InvalidArgument)?;
+            tier.purchase_count = tier
+                .purchase_count
+                .checked_add(1)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+        }
+
+        return Ok(());
     }
 }
 
@@ -40,16 +306,75 @@ impl IsInitialized for Batch0SaleProgramData {
 }
 
 impl Pack for Batch0SaleProgramData {
-    const LEN: usize = 105; // 1 + 8 + 32 + 32 + 32
+    const LEN: usize = 880; // 1 + 1 + 8 + 32 + 32 + 32 + 32 + 1 + 8 + 1 + 32 + 32 + 1 + 32 + 8 + 8 + 8 + 32 + 1 + 1 + 1 + 8 + 8 + 8 + 8 + 42 * MAX_TIERS + 10 * MAX_BONUS_BREAKPOINTS + STATE_RESERVED_LEN
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Batch0SaleProgramData::LEN];
         let (
+            version,
             is_initialized,
             current_price_bytes,
             inventory_pubkey,
             shelf_pubkey,
             till_pubkey,
-        ) = array_refs![src, 1, 8, 32, 32, 32]; 
+            mint_pubkey,
+            mint_decimals,
+            sale_id,
+            state_bump,
+            authority,
+            pending_authority,
+            authority_bump,
+            payment_mint_pubkey,
+            start_ts,
+            end_ts,
+            max_tokens_per_wallet,
+            merkle_root,
+            status,
+            receipts_enabled,
+            transfer_fee_mode,
+            total_tokens_sold,
+            total_bonus_issued,
+            total_collected,
+            total_purchases,
+            tiers_bytes,
+            bonus_schedule_bytes,
+            _reserved,
+        ) = array_refs![
+            src,
+            1,
+            1,
+            8,
+            32,
+            32,
+            32,
+            32,
+            1,
+            8,
+            1,
+            32,
+            32,
+            1,
+            32,
+            8,
+            8,
+            8,
+            32,
+            1,
+            1,
+            1,
+            8,
+            8,
+            8,
+            8,
+            PackageTier::LEN * MAX_TIERS,
+            BonusBreakpoint::LEN * MAX_BONUS_BREAKPOINTS,
+            STATE_RESERVED_LEN
+        ];
+
+        //older layouts go through unpack_any_version
+        let version = match version {
+            [STATE_VERSION] => STATE_VERSION,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
 
         let is_initialized = match is_initialized {
             [0] => false,
@@ -59,37 +384,411 @@ impl Pack for Batch0SaleProgramData {
 
         let current_price_per_token = u64::from_le_bytes(*current_price_bytes);
 
+        let mut tiers = [PackageTier::default(); MAX_TIERS];
+        for (tier, tier_bytes) in tiers.iter_mut().zip(tiers_bytes.chunks(PackageTier::LEN)) {
+            *tier = PackageTier::unpack_from_slice(tier_bytes)?;
+        }
+
+        let mut bonus_schedule = [BonusBreakpoint::default(); MAX_BONUS_BREAKPOINTS];
+        for (breakpoint, breakpoint_bytes) in bonus_schedule
+            .iter_mut()
+            .zip(bonus_schedule_bytes.chunks(BonusBreakpoint::LEN))
+        {
+            *breakpoint = BonusBreakpoint::unpack_from_slice(breakpoint_bytes)?;
+        }
+
         return Ok(Batch0SaleProgramData {
+            version,
             is_initialized,
             current_price_per_token,
             inventory_pubkey: Pubkey::new_from_array(*inventory_pubkey),
             shelf_pubkey: Pubkey::new_from_array(*shelf_pubkey),
             till_pubkey: Pubkey::new_from_array(*till_pubkey),
+            mint_pubkey: Pubkey::new_from_array(*mint_pubkey),
+            mint_decimals: mint_decimals[0],
+            sale_id: u64::from_le_bytes(*sale_id),
+            state_bump: state_bump[0],
+            authority: Pubkey::new_from_array(*authority),
+            pending_authority: Pubkey::new_from_array(*pending_authority),
+            authority_bump: authority_bump[0],
+            payment_mint_pubkey: Pubkey::new_from_array(*payment_mint_pubkey),
+            start_ts: i64::from_le_bytes(*start_ts),
+            end_ts: i64::from_le_bytes(*end_ts),
+            max_tokens_per_wallet: u64::from_le_bytes(*max_tokens_per_wallet),
+            merkle_root: *merkle_root,
+            status: SaleStatus::from_u8(status[0]).ok_or(ProgramError::InvalidAccountData)?,
+            receipts_enabled: match receipts_enabled {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            transfer_fee_mode: TransferFeeMode::from_u8(transfer_fee_mode[0])
+                .ok_or(ProgramError::InvalidAccountData)?,
+            total_tokens_sold: u64::from_le_bytes(*total_tokens_sold),
+            total_bonus_issued: u64::from_le_bytes(*total_bonus_issued),
+            total_collected: u64::from_le_bytes(*total_collected),
+            total_purchases: u64::from_le_bytes(*total_purchases),
+            tiers,
+            bonus_schedule,
         });
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, Batch0SaleProgramData::LEN];
         let (
+            version_dst,
             is_initialized_dst,
             current_price_per_token_dst,
             inventory_pubkey_dst,
             shelf_pubkey_dst,
             till_pubkey_dst,
-        ) = mut_array_refs![dst, 1, 8, 32, 32, 32];
+            mint_pubkey_dst,
+            mint_decimals_dst,
+            sale_id_dst,
+            state_bump_dst,
+            authority_dst,
+            pending_authority_dst,
+            authority_bump_dst,
+            payment_mint_pubkey_dst,
+            start_ts_dst,
+            end_ts_dst,
+            max_tokens_per_wallet_dst,
+            merkle_root_dst,
+            status_dst,
+            receipts_enabled_dst,
+            transfer_fee_mode_dst,
+            total_tokens_sold_dst,
+            total_bonus_issued_dst,
+            total_collected_dst,
+            total_purchases_dst,
+            tiers_dst,
+            bonus_schedule_dst,
+            reserved_dst,
+        ) = mut_array_refs![
+            dst,
+            1,
+            1,
+            8,
+            32,
+            32,
+            32,
+            32,
+            1,
+            8,
+            1,
+            32,
+            32,
+            1,
+            32,
+            8,
+            8,
+            8,
+            32,
+            1,
+            1,
+            1,
+            8,
+            8,
+            8,
+            8,
+            PackageTier::LEN * MAX_TIERS,
+            BonusBreakpoint::LEN * MAX_BONUS_BREAKPOINTS,
+            STATE_RESERVED_LEN
+        ];
 
         let Batch0SaleProgramData {
+            version,
             is_initialized,
             current_price_per_token,
             inventory_pubkey,
             shelf_pubkey,
-            till_pubkey
+            till_pubkey,
+            mint_pubkey,
+            mint_decimals,
+            sale_id,
+            state_bump,
+            authority,
+            pending_authority,
+            authority_bump,
+            payment_mint_pubkey,
+            start_ts,
+            end_ts,
+            max_tokens_per_wallet,
+            merkle_root,
+            status,
+            receipts_enabled,
+            transfer_fee_mode,
+            total_tokens_sold,
+            total_bonus_issued,
+            total_collected,
+            total_purchases,
+            tiers,
+            bonus_schedule,
         } = self;
 
+        version_dst[0] = *version;
         is_initialized_dst[0] = *is_initialized as u8;
         current_price_per_token_dst.copy_from_slice(&current_price_per_token.to_le_bytes());
         inventory_pubkey_dst.copy_from_slice(inventory_pubkey.as_ref());
         shelf_pubkey_dst.copy_from_slice(shelf_pubkey.as_ref());
         till_pubkey_dst.copy_from_slice(till_pubkey.as_ref());
+        mint_pubkey_dst.copy_from_slice(mint_pubkey.as_ref());
+        mint_decimals_dst[0] = *mint_decimals;
+        sale_id_dst.copy_from_slice(&sale_id.to_le_bytes());
+        state_bump_dst[0] = *state_bump;
+        authority_dst.copy_from_slice(authority.as_ref());
+        pending_authority_dst.copy_from_slice(pending_authority.as_ref());
+        authority_bump_dst[0] = *authority_bump;
+        payment_mint_pubkey_dst.copy_from_slice(payment_mint_pubkey.as_ref());
+        start_ts_dst.copy_from_slice(&start_ts.to_le_bytes());
+        end_ts_dst.copy_from_slice(&end_ts.to_le_bytes());
+        max_tokens_per_wallet_dst.copy_from_slice(&max_tokens_per_wallet.to_le_bytes());
+        merkle_root_dst.copy_from_slice(merkle_root);
+        status_dst[0] = *status as u8;
+        receipts_enabled_dst[0] = *receipts_enabled as u8;
+        transfer_fee_mode_dst[0] = *transfer_fee_mode as u8;
+        total_tokens_sold_dst.copy_from_slice(&total_tokens_sold.to_le_bytes());
+        total_bonus_issued_dst.copy_from_slice(&total_bonus_issued.to_le_bytes());
+        total_collected_dst.copy_from_slice(&total_collected.to_le_bytes());
+        total_purchases_dst.copy_from_slice(&total_purchases.to_le_bytes());
+        for (tier, tier_dst) in tiers.iter().zip(tiers_dst.chunks_mut(PackageTier::LEN)) {
+            tier.pack_into_slice(tier_dst);
+        }
+        for (breakpoint, breakpoint_dst) in bonus_schedule
+            .iter()
+            .zip(bonus_schedule_dst.chunks_mut(BonusBreakpoint::LEN))
+        {
+            breakpoint.pack_into_slice(breakpoint_dst);
+        }
+        reserved_dst.fill(0);
+    }
+}
+
+impl Batch0SaleProgramData {
+    //picks the layout from the version byte: the current one, or a v1 account
+    //upgraded in memory so MigrateState can rewrite it; callers check
+    //`version` before trusting it
+    pub fn unpack_any_version(src: &[u8]) -> Result<Self, ProgramError> {
+        return match src.first() {
+            Some(&STATE_VERSION) => Self::unpack(src),
+            Some(&STATE_V1) if src.len() == STATE_V1_LEN => Self::unpack_v1(src),
+            //a zeroed account was never initialized, whatever its length
+            Some(0) => Err(ProgramError::UninitializedAccount),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
+    }
+
+    //v1: is_initialized, price, inventory, shelf, till
+    fn unpack_v1(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, STATE_V1_LEN];
+        let (is_initialized, current_price_bytes, inventory_pubkey, shelf_pubkey, till_pubkey) =
+            array_refs![src, 1, 8, 32, 32, 32];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        if !is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let inventory_pubkey = Pubkey::new_from_array(*inventory_pubkey);
+        return Ok(Batch0SaleProgramData {
+            version: STATE_V1,
+            is_initialized,
+            current_price_per_token: u64::from_le_bytes(*current_price_bytes),
+            inventory_pubkey,
+            shelf_pubkey: Pubkey::new_from_array(*shelf_pubkey),
+            till_pubkey: Pubkey::new_from_array(*till_pubkey),
+            mint_pubkey: Pubkey::default(),
+            mint_decimals: 0,
+            //v1 state accounts were created by the caller, not at a PDA
+            sale_id: 0,
+            state_bump: 0,
+            authority: inventory_pubkey,
+            pending_authority: Pubkey::default(),
+            authority_bump: 0,
+            payment_mint_pubkey: Pubkey::default(),
+            start_ts: 0,
+            end_ts: 0,
+            max_tokens_per_wallet: 0,
+            merkle_root: [0u8; 32],
+            status: SaleStatus::Active,
+            receipts_enabled: false,
+            transfer_fee_mode: TransferFeeMode::GrossUp,
+            total_tokens_sold: 0,
+            total_bonus_issued: 0,
+            total_collected: 0,
+            total_purchases: 0,
+            tiers: PackageTier::default_tiers(),
+            bonus_schedule: BonusBreakpoint::default_schedule(),
+        });
+    }
+}
+
+//per-buyer running totals for one sale, kept in a PDA seeded by sale + buyer
+pub struct BuyerRecord {
+    pub is_initialized: bool,
+    pub sale_pubkey: Pubkey,
+    pub buyer_pubkey: Pubkey,
+    pub tokens_purchased: u64, // sold + bonus tokens delivered
+    pub total_paid: u64,       // lamports, or payment mint base units
+    pub bump_seed: u8,
+    pub purchase_count: u64, // index of the buyer's next receipt
+}
+
+impl Sealed for BuyerRecord {}
+
+impl IsInitialized for BuyerRecord {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for BuyerRecord {
+    const LEN: usize = 90; // 1 + 32 + 32 + 8 + 8 + 1 + 8
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, BuyerRecord::LEN];
+        let (
+            is_initialized,
+            sale_pubkey,
+            buyer_pubkey,
+            tokens_purchased,
+            total_paid,
+            bump_seed,
+            purchase_count,
+        ) = array_refs![src, 1, 32, 32, 8, 8, 1, 8];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        return Ok(BuyerRecord {
+            is_initialized,
+            sale_pubkey: Pubkey::new_from_array(*sale_pubkey),
+            buyer_pubkey: Pubkey::new_from_array(*buyer_pubkey),
+            tokens_purchased: u64::from_le_bytes(*tokens_purchased),
+            total_paid: u64::from_le_bytes(*total_paid),
+            bump_seed: bump_seed[0],
+            purchase_count: u64::from_le_bytes(*purchase_count),
+        });
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, BuyerRecord::LEN];
+        let (
+            is_initialized_dst,
+            sale_pubkey_dst,
+            buyer_pubkey_dst,
+            tokens_purchased_dst,
+            total_paid_dst,
+            bump_seed_dst,
+            purchase_count_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 1, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        sale_pubkey_dst.copy_from_slice(self.sale_pubkey.as_ref());
+        buyer_pubkey_dst.copy_from_slice(self.buyer_pubkey.as_ref());
+        tokens_purchased_dst.copy_from_slice(&self.tokens_purchased.to_le_bytes());
+        total_paid_dst.copy_from_slice(&self.total_paid.to_le_bytes());
+        bump_seed_dst[0] = self.bump_seed;
+        purchase_count_dst.copy_from_slice(&self.purchase_count.to_le_bytes());
+    }
+}
+
+//what one purchase delivered; returned to the caller via return data, and
+//kept in a receipt PDA seeded by sale + buyer + purchase index when the sale
+//has receipts enabled
+pub struct PurchaseReceipt {
+    pub is_initialized: bool,
+    pub sale_pubkey: Pubkey,
+    pub buyer_pubkey: Pubkey,
+    pub purchase_index: u64,
+    pub sold_tokens: u64,
+    pub bonus_tokens: u64,
+    pub amount_paid: u64, // lamports, or payment mint base units
+    pub price_per_token: u64,
+    pub slot: u64,
+    pub transfer_fee: u64, // withheld by the mint from the tokens sent to the buyer
+    pub recipient_pubkey: Pubkey, // wallet the tokens went to, the buyer unless gifted
+}
+
+impl Sealed for PurchaseReceipt {}
+
+impl IsInitialized for PurchaseReceipt {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PurchaseReceipt {
+    const LEN: usize = 153; // 1 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 32
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PurchaseReceipt::LEN];
+        let (
+            is_initialized,
+            sale_pubkey,
+            buyer_pubkey,
+            purchase_index,
+            sold_tokens,
+            bonus_tokens,
+            amount_paid,
+            price_per_token,
+            slot,
+            transfer_fee,
+            recipient_pubkey,
+        ) = array_refs![src, 1, 32, 32, 8, 8, 8, 8, 8, 8, 8, 32];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        return Ok(PurchaseReceipt {
+            is_initialized,
+            sale_pubkey: Pubkey::new_from_array(*sale_pubkey),
+            buyer_pubkey: Pubkey::new_from_array(*buyer_pubkey),
+            purchase_index: u64::from_le_bytes(*purchase_index),
+            sold_tokens: u64::from_le_bytes(*sold_tokens),
+            bonus_tokens: u64::from_le_bytes(*bonus_tokens),
+            amount_paid: u64::from_le_bytes(*amount_paid),
+            price_per_token: u64::from_le_bytes(*price_per_token),
+            slot: u64::from_le_bytes(*slot),
+            transfer_fee: u64::from_le_bytes(*transfer_fee),
+            recipient_pubkey: Pubkey::new_from_array(*recipient_pubkey),
+        });
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PurchaseReceipt::LEN];
+        let (
+            is_initialized_dst,
+            sale_pubkey_dst,
+            buyer_pubkey_dst,
+            purchase_index_dst,
+            sold_tokens_dst,
+            bonus_tokens_dst,
+            amount_paid_dst,
+            price_per_token_dst,
+            slot_dst,
+            transfer_fee_dst,
+            recipient_pubkey_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 8, 8, 8, 8, 8, 32];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        sale_pubkey_dst.copy_from_slice(self.sale_pubkey.as_ref());
+        buyer_pubkey_dst.copy_from_slice(self.buyer_pubkey.as_ref());
+        purchase_index_dst.copy_from_slice(&self.purchase_index.to_le_bytes());
+        sold_tokens_dst.copy_from_slice(&self.sold_tokens.to_le_bytes());
+        bonus_tokens_dst.copy_from_slice(&self.bonus_tokens.to_le_bytes());
+        amount_paid_dst.copy_from_slice(&self.amount_paid.to_le_bytes());
+        price_per_token_dst.copy_from_slice(&self.price_per_token.to_le_bytes());
+        slot_dst.copy_from_slice(&self.slot.to_le_bytes());
+        transfer_fee_dst.copy_from_slice(&self.transfer_fee.to_le_bytes());
+        recipient_pubkey_dst.copy_from_slice(self.recipient_pubkey.as_ref());
     }
 }
@@ -0,0 +1,103 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey, system_program,
+};
+
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account, Mint},
+};
+
+use crate::error::CustomError::{
+    self, AccountNotSigner, AccountNotWritable, IncorrectAccountOwner,
+    IncorrectAssociatedTokenProgram, IncorrectSystemProgram, IncorrectTokenProgram,
+    InvalidMintAccount, InvalidTokenAccount, MintMismatch,
+};
+
+pub fn assert_signer(account_info: &AccountInfo) -> ProgramResult {
+    if !account_info.is_signer {
+        msg!("{} must be a signer", account_info.key);
+        return Err(AccountNotSigner.into());
+    }
+
+    return Ok(());
+}
+
+pub fn assert_writable(account_info: &AccountInfo) -> ProgramResult {
+    if !account_info.is_writable {
+        msg!("{} must be writable", account_info.key);
+        return Err(AccountNotWritable.into());
+    }
+
+    return Ok(());
+}
+
+pub fn assert_owned_by(account_info: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+    if account_info.owner != owner {
+        msg!("{} is owned by {}, expected {}", account_info.key, account_info.owner, owner);
+        return Err(IncorrectAccountOwner.into());
+    }
+
+    return Ok(());
+}
+
+pub fn assert_keys_equal(actual: &Pubkey, expected: &Pubkey, error: CustomError) -> ProgramResult {
+    if actual != expected {
+        msg!("{} passed where {} was expected", actual, expected);
+        return Err(error.into());
+    }
+
+    return Ok(());
+}
+
+pub fn assert_token_2022_program(account_info: &AccountInfo) -> ProgramResult {
+    if *account_info.key != spl_token_2022::id() {
+        msg!("{} is not the Token-2022 program", account_info.key);
+        return Err(IncorrectTokenProgram.into());
+    }
+
+    return Ok(());
+}
+
+pub fn assert_system_program(account_info: &AccountInfo) -> ProgramResult {
+    if !system_program::check_id(account_info.key) {
+        msg!("{} is not the system program", account_info.key);
+        return Err(IncorrectSystemProgram.into());
+    }
+
+    return Ok(());
+}
+
+pub fn assert_associated_token_program(account_info: &AccountInfo) -> ProgramResult {
+    if !spl_associated_token_account_client::program::check_id(account_info.key) {
+        msg!("{} is not the associated token program", account_info.key);
+        return Err(IncorrectAssociatedTokenProgram.into());
+    }
+
+    return Ok(());
+}
+
+//Token-2022 mint account, returning its decimals
+pub fn assert_mint(account_info: &AccountInfo) -> Result<u8, ProgramError> {
+    assert_owned_by(account_info, &spl_token_2022::id())?;
+
+    let mint_data = account_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data).map_err(|_| InvalidMintAccount)?;
+
+    return Ok(mint.base.decimals);
+}
+
+//Token-2022 token account holding `mint`, returning its balance
+pub fn assert_token_account(account_info: &AccountInfo, mint: &Pubkey) -> Result<u64, ProgramError> {
+    assert_owned_by(account_info, &spl_token_2022::id())?;
+
+    let account_data = account_info.try_borrow_data()?;
+    let account =
+        StateWithExtensions::<Account>::unpack(&account_data).map_err(|_| InvalidTokenAccount)?;
+    if account.base.mint != *mint {
+        msg!("{} holds {}, expected {}", account_info.key, account.base.mint, mint);
+        return Err(MintMismatch.into());
+    }
+
+    return Ok(account.base.amount);
+}
@@ -0,0 +1,157 @@
+#![allow(clippy::needless_return)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::{
+    hash::Hash,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+
+use batch0_sale_solana::{
+    instruction::Batch0SaleInstruction,
+    pda::find_sale_state_address,
+    state::{Batch0SaleProgramData, STATE_VERSION},
+};
+
+//a scratch directory per test, so tests can run in parallel
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("batch0-sale-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    return dir;
+}
+
+fn keypair_file(dir: &Path, name: &str) -> (Keypair, String) {
+    let keypair = Keypair::new();
+    let path = dir.join(name);
+    write_keypair_file(&keypair, &path).unwrap();
+    return (keypair, path.to_str().unwrap().to_string());
+}
+
+fn run(args: &[&str]) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_batch0-sale"))
+        //nothing listens here, so any RPC call would fail the test
+        .args(["--url", "http://127.0.0.1:1"])
+        .args(args)
+        .output()
+        .unwrap();
+}
+
+#[test]
+fn offline_init_prints_a_signed_transaction() {
+    let dir = scratch_dir("init");
+    let (payer, payer_path) = keypair_file(&dir, "payer.json");
+    let (shelf, shelf_path) = keypair_file(&dir, "shelf.json");
+    let program_id = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let till = Pubkey::new_unique();
+    let blockhash = Hash::new_from_array([7; 32]);
+
+    let output = run(&[
+        "--program-id",
+        &program_id.to_string(),
+        "--keypair",
+        &payer_path,
+        "--offline",
+        "--blockhash",
+        &blockhash.to_string(),
+        "init",
+        "--shelf-keypair",
+        &shelf_path,
+        "--till",
+        &till.to_string(),
+        "--mint",
+        &mint.to_string(),
+        "--price",
+        "2",
+        "--sale-id",
+        "3",
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let (sale, _) = find_sale_state_address(&mint, &payer.pubkey(), 3, &program_id);
+    assert_eq!(lines.next().unwrap(), format!("Sale state: {}", sale));
+
+    let transaction: Transaction =
+        bincode::deserialize(&STANDARD.decode(lines.next().unwrap()).unwrap()).unwrap();
+    transaction.verify().unwrap();
+    assert_eq!(transaction.message.recent_blockhash, blockhash);
+    assert_eq!(transaction.message.account_keys[0], payer.pubkey());
+    assert!(transaction.message.account_keys.contains(&shelf.pubkey()));
+    assert!(transaction.message.account_keys.contains(&sale));
+
+    let instruction = &transaction.message.instructions[0];
+    assert_eq!(
+        Batch0SaleInstruction::unpack(&instruction.data).unwrap(),
+        Batch0SaleInstruction::InitSale {
+            new_price_per_token: 2,
+            start_ts: 0,
+            end_ts: 0,
+            sale_id: 3,
+        }
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn offline_mode_requires_a_blockhash() {
+    let dir = scratch_dir("blockhash");
+    let (_, payer_path) = keypair_file(&dir, "payer.json");
+    let sale = Pubkey::new_unique().to_string();
+
+    let output = run(&[
+        "--program-id",
+        &Pubkey::new_unique().to_string(),
+        "--keypair",
+        &payer_path,
+        "--offline",
+        "pause",
+        &sale,
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--blockhash"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn offline_show_decodes_sale_data_from_a_file() {
+    let dir = scratch_dir("show");
+    let sale_data_path = dir.join("sale.bin");
+
+    //version, is_initialized and price; a zeroed remainder decodes to defaults
+    let mut sale_data = vec![0u8; Batch0SaleProgramData::LEN];
+    sale_data[0] = STATE_VERSION;
+    sale_data[1] = 1;
+    sale_data[2..10].copy_from_slice(&5u64.to_le_bytes());
+    Batch0SaleProgramData::unpack(&sale_data).unwrap();
+    fs::write(&sale_data_path, &sale_data).unwrap();
+
+    let output = run(&[
+        "--program-id",
+        &Pubkey::new_unique().to_string(),
+        "--offline",
+        "--sale-data",
+        sale_data_path.to_str().unwrap(),
+        "show",
+        &Pubkey::new_unique().to_string(),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("Version:               {}", STATE_VERSION)));
+    assert!(stdout.contains("Price per token:       5"));
+    assert!(stdout.contains("Status:                Active"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
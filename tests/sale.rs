@@ -0,0 +1,1465 @@
+#![allow(clippy::needless_return)]
+
+use std::convert::TryInto;
+
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    keccak,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_2022::{
+    extension::{
+        transfer_fee::instruction::initialize_transfer_fee_config,
+        transfer_hook::instruction::initialize as initialize_transfer_hook,
+        BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+    },
+    instruction::AuthorityType,
+    state::{Account, Mint},
+};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+
+use batch0_sale_solana::{
+    error::CustomError,
+    merkle::allowlist_leaf,
+    instruction::{self, BuyAccounts, Batch0SaleInstruction, PaymentAccounts},
+    pda::{
+        find_buyer_record_address, find_legacy_sale_authority_address, find_receipt_address,
+        find_sale_authority_address, find_sale_state_address,
+    },
+    processor::Processor,
+    state::{
+        Batch0SaleProgramData, BonusBreakpoint, BuyerRecord, PackageTier, PurchaseReceipt,
+        SaleStatus, TransferFeeMode,
+        MAX_BONUS_BREAKPOINTS, STATE_VERSION, TIER_NAME_LEN,
+    },
+};
+
+const DECIMALS: u8 = 2;
+const SHELF_SUPPLY: u64 = 10_000_000;
+const PRICE: u64 = 1;
+const BUYER_LAMPORTS: u64 = 100_000_000_000;
+const PAYMENT_SUPPLY: u64 = 1_000_000;
+
+//a stand-in for spl-transfer-hook-example, which has no release built against
+//this Solana version: Execute checks its accounts against the mint's
+//extra-account-metas PDA the same way, then counts the call in the one extra
+//account that PDA lists
+fn process_transfer_hook(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    match TransferHookInstruction::unpack(input)? {
+        TransferHookInstruction::Execute { .. } => {}
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+
+    //source, mint, destination, authority, extra-account-metas PDA, counter
+    let mint_info = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let extra_account_metas_info = accounts.get(4).ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if *extra_account_metas_info.key != get_extra_account_metas_address(mint_info.key, program_id) {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    ExtraAccountMetaList::check_account_infos::<ExecuteInstruction>(
+        accounts,
+        input,
+        program_id,
+        &extra_account_metas_info.try_borrow_data()?,
+    )?;
+
+    let mut counter = accounts[5].try_borrow_mut_data()?;
+    let calls = u64::from_le_bytes(counter[..8].try_into().unwrap()) + 1;
+    counter[..8].copy_from_slice(&calls.to_le_bytes());
+    return Ok(());
+}
+
+struct TransferHook {
+    program_id: Pubkey,
+    counter: Pubkey,
+}
+
+impl TransferHook {
+    fn new() -> Self {
+        return TransferHook {
+            program_id: Pubkey::new_unique(),
+            counter: Pubkey::new_unique(),
+        };
+    }
+
+    //registers the hook program, its call counter and the mint's
+    //extra-account-metas PDA listing that counter
+    fn add_to(&self, program_test: &mut ProgramTest, mint: &Pubkey) {
+        program_test.add_program(
+            "transfer_hook",
+            self.program_id,
+            processor!(process_transfer_hook),
+        );
+        program_test.add_account(
+            self.counter,
+            SolanaAccount::new(1_000_000_000, 8, &self.program_id),
+        );
+
+        let extra_account_metas =
+            [ExtraAccountMeta::new_with_pubkey(&self.counter, false, true).unwrap()];
+        let mut data =
+            vec![0u8; ExtraAccountMetaList::size_of(extra_account_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &extra_account_metas).unwrap();
+        let mut account = SolanaAccount::new(1_000_000_000, 0, &self.program_id);
+        account.data = data;
+        program_test.add_account(get_extra_account_metas_address(mint, &self.program_id), account);
+    }
+
+    //what a buy or EndTokenSale passes as `extra_accounts`
+    fn extra_accounts(&self, mint: &Pubkey) -> Vec<AccountMeta> {
+        return vec![
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(get_extra_account_metas_address(mint, &self.program_id), false),
+            AccountMeta::new(self.counter, false),
+        ];
+    }
+}
+
+struct SaleTest {
+    context: ProgramTestContext,
+    program_id: Pubkey,
+    mint: Pubkey,
+    shelf: Keypair,
+    till: Pubkey,
+    buyer: Keypair,
+    buyer_token_account: Pubkey,
+    inventory_token_account: Pubkey,
+    sale: Pubkey,
+}
+
+impl SaleTest {
+    async fn new() -> Self {
+        return Self::with_mint_extensions(None, None).await;
+    }
+
+    async fn with_transfer_fee(transfer_fee_bps: Option<u16>) -> Self {
+        return Self::with_mint_extensions(transfer_fee_bps, None).await;
+    }
+
+    //a funded buyer and till, a Token-2022 mint (charging `transfer_fee_bps`
+    //on transfers when set, and calling `transfer_hook` on them when set), the
+    //stocked shelf and token accounts for the buyer and inventory; the sale is
+    //not yet created
+    async fn with_mint_extensions(
+        transfer_fee_bps: Option<u16>,
+        transfer_hook: Option<&TransferHook>,
+    ) -> Self {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "batch0_sale_solana",
+            program_id,
+            processor!(Processor::process),
+        );
+        program_test.prefer_bpf(false);
+
+        let buyer = Keypair::new();
+        program_test.add_account(
+            buyer.pubkey(),
+            SolanaAccount::new(BUYER_LAMPORTS, 0, &solana_sdk::system_program::id()),
+        );
+        //pre-funded so small payments clear the rent-exempt minimum
+        let till = Pubkey::new_unique();
+        program_test.add_account(
+            till,
+            SolanaAccount::new(1_000_000_000, 0, &solana_sdk::system_program::id()),
+        );
+
+        let mint = Keypair::new();
+        if let Some(transfer_hook) = transfer_hook {
+            transfer_hook.add_to(&mut program_test, &mint.pubkey());
+        }
+
+        let mut context = program_test.start_with_context().await;
+
+        let payer = context.payer.insecure_clone();
+        let rent = context.banks_client.get_rent().await.unwrap();
+        let mut extensions = vec![];
+        if transfer_fee_bps.is_some() {
+            extensions.push(ExtensionType::TransferFeeConfig);
+        }
+        if transfer_hook.is_some() {
+            extensions.push(ExtensionType::TransferHook);
+        }
+        let mint_len = ExtensionType::try_calculate_account_len::<Mint>(&extensions).unwrap();
+        let mut instructions = vec![system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(mint_len),
+            mint_len as u64,
+            &spl_token_2022::id(),
+        )];
+        if let Some(transfer_fee_bps) = transfer_fee_bps {
+            instructions.push(
+                initialize_transfer_fee_config(
+                    &spl_token_2022::id(),
+                    &mint.pubkey(),
+                    None,
+                    None,
+                    transfer_fee_bps,
+                    u64::MAX,
+                )
+                .unwrap(),
+            );
+        }
+        if let Some(transfer_hook) = transfer_hook {
+            instructions.push(
+                initialize_transfer_hook(
+                    &spl_token_2022::id(),
+                    &mint.pubkey(),
+                    None,
+                    Some(transfer_hook.program_id),
+                )
+                .unwrap(),
+            );
+        }
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(
+                &spl_token_2022::id(),
+                &mint.pubkey(),
+                &payer.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        );
+        send(&mut context, &instructions, &[&mint]).await.unwrap();
+
+        //the shelf starts out owning itself, InitSale hands it to the sale
+        let shelf = Keypair::new();
+        create_token_account(&mut context, &shelf, &mint.pubkey(), &shelf.pubkey()).await;
+        send(
+            &mut context,
+            &[spl_token_2022::instruction::mint_to(
+                &spl_token_2022::id(),
+                &mint.pubkey(),
+                &shelf.pubkey(),
+                &payer.pubkey(),
+                &[],
+                SHELF_SUPPLY,
+            )
+            .unwrap()],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let buyer_token_account = Keypair::new();
+        create_token_account(&mut context, &buyer_token_account, &mint.pubkey(), &buyer.pubkey())
+            .await;
+        let inventory_token_account = Keypair::new();
+        create_token_account(
+            &mut context,
+            &inventory_token_account,
+            &mint.pubkey(),
+            &payer.pubkey(),
+        )
+        .await;
+
+        let (sale, _) =
+            find_sale_state_address(&mint.pubkey(), &context.payer.pubkey(), 0, &program_id);
+
+        return SaleTest {
+            context,
+            program_id,
+            mint: mint.pubkey(),
+            shelf,
+            till,
+            buyer,
+            buyer_token_account: buyer_token_account.pubkey(),
+            inventory_token_account: inventory_token_account.pubkey(),
+            sale,
+        };
+    }
+
+    async fn with_sale() -> Self {
+        let mut test = Self::new().await;
+        test.init_sale().await.unwrap();
+        return test;
+    }
+
+    async fn init_sale(&mut self) -> Result<(), BanksClientError> {
+        let shelf = self.shelf.insecure_clone();
+        let ix = instruction::init_sale(
+            &self.program_id,
+            &self.context.payer.pubkey(),
+            &self.till,
+            &shelf.pubkey(),
+            &self.mint,
+            PRICE,
+            0,
+            0,
+            0,
+        );
+        return send(&mut self.context, &[ix], &[&shelf]).await;
+    }
+
+    async fn sale_data(&mut self) -> Batch0SaleProgramData {
+        let account = self.context.banks_client.get_account(self.sale).await.unwrap().unwrap();
+        return Batch0SaleProgramData::unpack(&account.data).unwrap();
+    }
+
+    fn buy_accounts(&self) -> BuyAccounts {
+        return BuyAccounts {
+            buyer: self.buyer.pubkey(),
+            sale: self.sale,
+            buyer_token_account: self.buyer_token_account,
+            purchase_index: 0,
+            payment: None,
+            create_buyer_token_account: false,
+            recipient: None,
+            extra_accounts: vec![],
+        };
+    }
+
+    async fn buy_package_ix(&mut self, tier_id: u8) -> Instruction {
+        let sale_data = self.sale_data().await;
+        return instruction::buy_package(
+            &self.program_id,
+            &self.buy_accounts(),
+            &sale_data,
+            tier_id,
+            u64::MAX,
+            0,
+        );
+    }
+
+    async fn buy_tokens_ix(&mut self, amount: u64) -> Instruction {
+        let sale_data = self.sale_data().await;
+        return instruction::buy_tokens(
+            &self.program_id,
+            &self.buy_accounts(),
+            &sale_data,
+            amount,
+            u64::MAX,
+            0,
+        );
+    }
+
+    //an admin change signed by the sale authority
+    async fn update_sale(&mut self, ix: Batch0SaleInstruction) -> Result<(), BanksClientError> {
+        let ix = instruction::update_sale(
+            &self.program_id,
+            &self.context.payer.pubkey(),
+            &self.sale,
+            ix,
+        );
+        return send(&mut self.context, &[ix], &[]).await;
+    }
+
+    async fn buy(&mut self, ix: Instruction) -> Result<(), BanksClientError> {
+        let buyer = self.buyer.insecure_clone();
+        return send(&mut self.context, &[ix], &[&buyer]).await;
+    }
+
+    async fn token_balance(&mut self, token_account: Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(token_account).await.unwrap().unwrap();
+        return StateWithExtensions::<Account>::unpack(&account.data).unwrap().base.amount;
+    }
+
+    async fn lamports(&mut self, pubkey: Pubkey) -> u64 {
+        return self.context.banks_client.get_balance(pubkey).await.unwrap();
+    }
+
+    //prices the sale in a new mint owned by `token_program`, paid into a fresh
+    //till, and funds a buyer token account of it; returns that account
+    async fn switch_to_payment_mint(&mut self, token_program: &Pubkey, price: u64) -> Pubkey {
+        let payment_mint = create_mint(&mut self.context, token_program).await;
+        let payer = self.context.payer.pubkey();
+
+        let till = Keypair::new();
+        create_token_account(&mut self.context, &till, &payment_mint, &payer).await;
+        let buyer_payment_account = Keypair::new();
+        create_token_account(
+            &mut self.context,
+            &buyer_payment_account,
+            &payment_mint,
+            &self.buyer.pubkey(),
+        )
+        .await;
+        send(
+            &mut self.context,
+            &[spl_token_2022::instruction::mint_to(
+                token_program,
+                &payment_mint,
+                &buyer_payment_account.pubkey(),
+                &payer,
+                &[],
+                PAYMENT_SUPPLY,
+            )
+            .unwrap()],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let ix = instruction::set_payment_mint(
+            &self.program_id,
+            &payer,
+            &self.sale,
+            &till.pubkey(),
+            Some(&payment_mint),
+            price,
+        );
+        send(&mut self.context, &[ix], &[]).await.unwrap();
+        self.till = till.pubkey();
+
+        return buyer_payment_account.pubkey();
+    }
+
+    async fn unix_timestamp(&mut self) -> i64 {
+        return self.context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    }
+
+    async fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        let mut clock = self.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    //sends lamports to an address the program has yet to create, as anyone could
+    async fn prefund(&mut self, pubkey: Pubkey) {
+        let payer = self.context.payer.pubkey();
+        let ix = system_instruction::transfer(&payer, &pubkey, 1_000_000);
+        send(&mut self.context, &[ix], &[]).await.unwrap();
+    }
+}
+
+async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    return context.banks_client.process_transaction(transaction).await;
+}
+
+async fn create_token_account(
+    context: &mut ProgramTestContext,
+    account: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) {
+    let payer = context.payer.insecure_clone();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let mint_account = context.banks_client.get_account(*mint).await.unwrap().unwrap();
+    let token_program = mint_account.owner;
+    let mint_extensions = StateWithExtensions::<Mint>::unpack(&mint_account.data)
+        .unwrap()
+        .get_extension_types()
+        .unwrap();
+    let account_len = ExtensionType::try_calculate_account_len::<Account>(
+        &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+    )
+    .unwrap();
+    send(
+        context,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(account_len),
+                account_len as u64,
+                &token_program,
+            ),
+            spl_token_2022::instruction::initialize_account3(
+                &token_program,
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[account],
+    )
+    .await
+    .unwrap();
+}
+
+//a plain mint owned by `token_program`, with the payer as mint authority
+async fn create_mint(context: &mut ProgramTestContext, token_program: &Pubkey) -> Pubkey {
+    let mint = Keypair::new();
+    let payer = context.payer.insecure_clone();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    send(
+        context,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(Mint::LEN),
+                Mint::LEN as u64,
+                token_program,
+            ),
+            spl_token_2022::instruction::initialize_mint2(
+                token_program,
+                &mint.pubkey(),
+                &payer.pubkey(),
+                None,
+                6,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    return mint.pubkey();
+}
+
+fn assert_custom_error(result: Result<(), BanksClientError>, error: CustomError) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+    );
+}
+
+#[tokio::test]
+async fn init_sale_creates_state_and_takes_the_shelf() {
+    let mut test = SaleTest::with_sale().await;
+
+    let sale_data = test.sale_data().await;
+    assert!(sale_data.is_initialized);
+    assert_eq!(sale_data.current_price_per_token, PRICE);
+    assert_eq!(sale_data.inventory_pubkey, test.context.payer.pubkey());
+    assert_eq!(sale_data.authority, test.context.payer.pubkey());
+    assert_eq!(sale_data.shelf_pubkey, test.shelf.pubkey());
+    assert_eq!(sale_data.till_pubkey, test.till);
+    assert_eq!(sale_data.mint_pubkey, test.mint);
+    assert_eq!(sale_data.mint_decimals, DECIMALS);
+
+    let account = test.context.banks_client.get_account(test.sale).await.unwrap().unwrap();
+    assert_eq!(account.owner, test.program_id);
+    assert_eq!(account.data.len(), Batch0SaleProgramData::LEN);
+}
+
+#[tokio::test]
+async fn init_sale_twice_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    assert_eq!(
+        test.init_sale().await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+    );
+}
+
+#[tokio::test]
+async fn init_sale_with_a_prefunded_state_account_succeeds() {
+    let mut test = SaleTest::new().await;
+    test.prefund(test.sale).await;
+
+    test.init_sale().await.unwrap();
+    assert!(test.sale_data().await.is_initialized);
+}
+
+#[tokio::test]
+async fn init_sale_by_another_inventory_cannot_take_the_sale_id() {
+    let mut test = SaleTest::new().await;
+
+    //someone else opens sale id 0 of the same mint with their own shelf
+    let squatter = Keypair::new();
+    let squatter_shelf = Keypair::new();
+    let payer = test.context.payer.pubkey();
+    let ix = system_instruction::transfer(&payer, &squatter.pubkey(), 1_000_000_000);
+    send(&mut test.context, &[ix], &[]).await.unwrap();
+    create_token_account(&mut test.context, &squatter_shelf, &test.mint, &squatter_shelf.pubkey())
+        .await;
+    let ix = instruction::init_sale(
+        &test.program_id,
+        &squatter.pubkey(),
+        &squatter.pubkey(),
+        &squatter_shelf.pubkey(),
+        &test.mint,
+        PRICE,
+        0,
+        0,
+        0,
+    );
+    send(&mut test.context, &[ix], &[&squatter, &squatter_shelf]).await.unwrap();
+
+    test.init_sale().await.unwrap();
+    assert_eq!(test.sale_data().await.inventory_pubkey, payer);
+}
+
+#[tokio::test]
+async fn buys_every_default_tier() {
+    let mut test = SaleTest::with_sale().await;
+
+    let mut delivered = 0;
+    let mut paid = 0;
+    for (tier_id, tier) in PackageTier::default_tiers().iter().enumerate() {
+        if !tier.is_initialized {
+            continue;
+        }
+        let till_before = test.lamports(test.till).await;
+
+        let ix = test.buy_package_ix(tier_id as u8).await;
+        test.buy(ix).await.unwrap();
+
+        delivered += tier.sold_tokens + tier.bonus_tokens;
+        paid += tier.sold_tokens * PRICE;
+        assert_eq!(test.token_balance(test.buyer_token_account).await, delivered);
+        assert_eq!(test.lamports(test.till).await - till_before, tier.sold_tokens * PRICE);
+    }
+
+    assert_eq!(test.token_balance(test.shelf.pubkey()).await, SHELF_SUPPLY - delivered);
+
+    let sale_data = test.sale_data().await;
+    assert_eq!(sale_data.total_tokens_sold + sale_data.total_bonus_issued, delivered);
+    assert_eq!(sale_data.total_collected, paid);
+    assert_eq!(sale_data.total_purchases, 6);
+    assert!(sale_data.tiers[..6].iter().all(|tier| tier.purchase_count == 1));
+}
+
+#[tokio::test]
+async fn buying_with_a_prefunded_buyer_record_succeeds() {
+    let mut test = SaleTest::with_sale().await;
+    let (buyer_record, _) =
+        find_buyer_record_address(&test.sale, &test.buyer.pubkey(), &test.program_id);
+    test.prefund(buyer_record).await;
+
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+
+    let account = test.context.banks_client.get_account(buyer_record).await.unwrap().unwrap();
+    assert_eq!(account.owner, test.program_id);
+    assert_eq!(BuyerRecord::unpack(&account.data).unwrap().purchase_count, 1);
+}
+
+#[tokio::test]
+async fn buying_with_a_prefunded_receipt_succeeds() {
+    let mut test = SaleTest::with_sale().await;
+    test.update_sale(Batch0SaleInstruction::SetReceiptsEnabled {
+        receipts_enabled: true,
+    })
+    .await
+    .unwrap();
+    let (receipt, _) = find_receipt_address(&test.sale, &test.buyer.pubkey(), 0, &test.program_id);
+    test.prefund(receipt).await;
+
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+
+    let account = test.context.banks_client.get_account(receipt).await.unwrap().unwrap();
+    assert_eq!(account.owner, test.program_id);
+    let receipt = PurchaseReceipt::unpack(&account.data).unwrap();
+    assert_eq!(receipt.buyer_pubkey, test.buyer.pubkey());
+    assert_eq!(receipt.sold_tokens, PackageTier::default_tiers()[0].sold_tokens);
+}
+
+#[tokio::test]
+async fn buying_an_unknown_tier_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    let ix = test.buy_package_ix(7).await;
+    assert_custom_error(test.buy(ix).await, CustomError::InvalidTier);
+}
+
+#[tokio::test]
+async fn tiers_must_sell_at_least_one_token() {
+    let mut test = SaleTest::with_sale().await;
+
+    let mut name = [0u8; TIER_NAME_LEN];
+    name[..7].copy_from_slice(b"Starter");
+    let bonus_tokens = 50;
+    assert_custom_error(
+        test.update_sale(Batch0SaleInstruction::AddTier {
+            tier_id: 7,
+            name,
+            sold_tokens: 0,
+            bonus_tokens,
+        })
+        .await,
+        CustomError::InvalidPurchaseAmount,
+    );
+    assert_custom_error(
+        test.update_sale(Batch0SaleInstruction::EditTier {
+            tier_id: 0,
+            name,
+            sold_tokens: 0,
+            bonus_tokens,
+        })
+        .await,
+        CustomError::InvalidPurchaseAmount,
+    );
+
+    test.update_sale(Batch0SaleInstruction::AddTier {
+        tier_id: 7,
+        name,
+        sold_tokens: 10,
+        bonus_tokens,
+    })
+    .await
+    .unwrap();
+    let ix = test.buy_package_ix(7).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 10 + bonus_tokens);
+}
+
+#[tokio::test]
+async fn buy_tokens_earns_the_best_bonus_breakpoint_reached() {
+    let mut test = SaleTest::with_sale().await;
+
+    //300000 reaches the 2330 bps breakpoint
+    let till_before = test.lamports(test.till).await;
+    let ix = test.buy_tokens_ix(300000).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 300000 + 69900);
+    assert_eq!(test.lamports(test.till).await - till_before, 300000 * PRICE);
+
+    //one short of it only reaches 2000 bps, rounded down
+    let ix = test.buy_tokens_ix(299999).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(
+        test.token_balance(test.buyer_token_account).await,
+        300000 + 69900 + 299999 + 59999
+    );
+    assert_eq!(test.lamports(test.till).await - till_before, 599999 * PRICE);
+
+    let sale_data = test.sale_data().await;
+    assert_eq!(sale_data.total_tokens_sold, 599999);
+    assert_eq!(sale_data.total_bonus_issued, 69900 + 59999);
+    assert_eq!(sale_data.total_collected, 599999 * PRICE);
+    assert_eq!(
+        test.token_balance(test.shelf.pubkey()).await,
+        SHELF_SUPPLY - 599999 - 69900 - 59999
+    );
+}
+
+#[tokio::test]
+async fn buying_zero_tokens_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    let ix = test.buy_tokens_ix(0).await;
+    assert_custom_error(test.buy(ix).await, CustomError::InvalidPurchaseAmount);
+}
+
+#[tokio::test]
+async fn bonus_breakpoints_are_capped_at_10000_bps() {
+    let mut test = SaleTest::with_sale().await;
+
+    let mut bonus_schedule = [BonusBreakpoint::default(); MAX_BONUS_BREAKPOINTS];
+    bonus_schedule[0] = BonusBreakpoint { min_tokens: 1, bonus_bps: 10001 };
+    assert_custom_error(
+        test.update_sale(Batch0SaleInstruction::SetBonusSchedule { bonus_schedule }).await,
+        CustomError::InvalidBonusSchedule,
+    );
+
+    bonus_schedule[0].bonus_bps = 10000;
+    test.update_sale(Batch0SaleInstruction::SetBonusSchedule { bonus_schedule })
+        .await
+        .unwrap();
+    assert_eq!(test.sale_data().await.bonus_schedule, bonus_schedule);
+
+    let ix = test.buy_tokens_ix(100).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 200);
+}
+
+#[tokio::test]
+async fn presale_buys_need_a_valid_proof_within_the_allocation() {
+    let mut test = SaleTest::with_sale().await;
+    let tier = PackageTier::default_tiers()[0];
+    let allocation = tier.sold_tokens + tier.bonus_tokens;
+
+    let buyer_leaf = allowlist_leaf(&test.buyer.pubkey(), allocation);
+    let other_leaf = allowlist_leaf(&Pubkey::new_unique(), 0);
+    let (low, high) = if buyer_leaf <= other_leaf {
+        (buyer_leaf, other_leaf)
+    } else {
+        (other_leaf, buyer_leaf)
+    };
+    let merkle_root = keccak::hashv(&[&low, &high]).to_bytes();
+    test.update_sale(Batch0SaleInstruction::SetMerkleRoot { merkle_root }).await.unwrap();
+
+    let ix = test.buy_package_ix(0).await;
+    assert_custom_error(test.buy(ix).await, CustomError::PresaleProofRequired);
+
+    let sale_data = test.sale_data().await;
+    let buy_with_proof = |allocation: u64| {
+        instruction::buy_package_with_proof(
+            &test.program_id,
+            &test.buy_accounts(),
+            &sale_data,
+            0,
+            u64::MAX,
+            0,
+            allocation,
+            vec![other_leaf],
+        )
+        .unwrap()
+    };
+    let overclaimed = buy_with_proof(allocation * 2);
+    let valid = buy_with_proof(allocation);
+    let repeated = buy_with_proof(allocation);
+
+    assert_custom_error(test.buy(overclaimed).await, CustomError::InvalidMerkleProof);
+    test.buy(valid).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, allocation);
+    assert_custom_error(test.buy(repeated).await, CustomError::AllocationExceeded);
+}
+
+#[tokio::test]
+async fn migrate_state_upgrades_a_v1_sale() {
+    let mut test = SaleTest::new().await;
+    let payer = test.context.payer.insecure_clone();
+    let (legacy_sale_authority, _) = find_legacy_sale_authority_address(&test.program_id);
+
+    //v1 sales all handed their shelf to the one legacy PDA
+    let shelf = test.shelf.insecure_clone();
+    let ix = spl_token_2022::instruction::set_authority(
+        &spl_token_2022::id(),
+        &shelf.pubkey(),
+        Some(&legacy_sale_authority),
+        AuthorityType::AccountOwner,
+        &shelf.pubkey(),
+        &[],
+    )
+    .unwrap();
+    send(&mut test.context, &[ix], &[&shelf]).await.unwrap();
+
+    //is_initialized, price, inventory, shelf, till
+    let mut v1_data = vec![1u8];
+    v1_data.extend_from_slice(&PRICE.to_le_bytes());
+    v1_data.extend_from_slice(payer.pubkey().as_ref());
+    v1_data.extend_from_slice(shelf.pubkey().as_ref());
+    v1_data.extend_from_slice(test.till.as_ref());
+    let rent = test.context.banks_client.get_rent().await.unwrap();
+    let mut v1_account =
+        SolanaAccount::new(rent.minimum_balance(v1_data.len()), v1_data.len(), &test.program_id);
+    v1_account.data = v1_data;
+    test.sale = Pubkey::new_unique();
+    test.context.set_account(&test.sale, &v1_account.into());
+
+    let ix = instruction::update_price(&test.program_id, &payer.pubkey(), &test.sale, PRICE * 2);
+    assert_custom_error(
+        send(&mut test.context, &[ix], &[]).await,
+        CustomError::StateMigrationRequired,
+    );
+
+    //a zeroed account of the current size is uninitialized, not an old layout
+    let zeroed = Pubkey::new_unique();
+    let zeroed_account = SolanaAccount::new(
+        rent.minimum_balance(Batch0SaleProgramData::LEN),
+        Batch0SaleProgramData::LEN,
+        &test.program_id,
+    );
+    test.context.set_account(&zeroed, &zeroed_account.into());
+    let ix = instruction::update_price(&test.program_id, &payer.pubkey(), &zeroed, PRICE * 2);
+    assert_eq!(
+        send(&mut test.context, &[ix], &[]).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::UninitializedAccount)
+    );
+
+    let migrate_ix = || {
+        instruction::migrate_state(
+            &test.program_id,
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &test.sale,
+            &shelf.pubkey(),
+            &test.mint,
+        )
+    };
+    let (first, second) = (migrate_ix(), migrate_ix());
+    send(&mut test.context, &[first], &[]).await.unwrap();
+
+    let account = test.context.banks_client.get_account(test.sale).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), Batch0SaleProgramData::LEN);
+    assert!(account.lamports >= rent.minimum_balance(Batch0SaleProgramData::LEN));
+    let sale_data = test.sale_data().await;
+    assert_eq!(sale_data.version, STATE_VERSION);
+    assert_eq!(sale_data.current_price_per_token, PRICE);
+    assert_eq!(sale_data.authority, payer.pubkey());
+    assert_eq!(sale_data.mint_pubkey, test.mint);
+    assert_eq!(sale_data.mint_decimals, DECIMALS);
+
+    let shelf_account = test.context.banks_client.get_account(shelf.pubkey()).await.unwrap();
+    let shelf_owner =
+        StateWithExtensions::<Account>::unpack(&shelf_account.unwrap().data).unwrap().base.owner;
+    assert_eq!(shelf_owner, find_sale_authority_address(&test.sale, &test.program_id).0);
+
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 1000);
+
+    assert_custom_error(
+        send(&mut test.context, &[second], &[]).await,
+        CustomError::StateAlreadyCurrent,
+    );
+}
+
+#[tokio::test]
+async fn buys_are_only_accepted_inside_the_sale_window() {
+    let mut test = SaleTest::with_sale().await;
+    let now = test.unix_timestamp().await;
+
+    test.update_sale(Batch0SaleInstruction::SetSaleWindow {
+        start_ts: now + 100,
+        end_ts: now + 200,
+    })
+    .await
+    .unwrap();
+    let sale_data = test.sale_data().await;
+    assert_eq!((sale_data.start_ts, sale_data.end_ts), (now + 100, now + 200));
+
+    let ix = test.buy_package_ix(0).await;
+    assert_custom_error(test.buy(ix).await, CustomError::SaleNotStarted);
+
+    test.set_unix_timestamp(now + 100).await;
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+
+    //end_ts itself is already closed
+    test.set_unix_timestamp(now + 200).await;
+    let ix = test.buy_package_ix(0).await;
+    assert_custom_error(test.buy(ix).await, CustomError::SaleEnded);
+
+    //the authority can extend the sale, or leave its end open
+    test.update_sale(Batch0SaleInstruction::SetSaleWindow {
+        start_ts: now + 100,
+        end_ts: 0,
+    })
+    .await
+    .unwrap();
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 2000);
+}
+
+#[tokio::test]
+async fn sale_windows_must_not_be_empty() {
+    let mut test = SaleTest::with_sale().await;
+
+    assert_custom_error(
+        test.update_sale(Batch0SaleInstruction::SetSaleWindow {
+            start_ts: 200,
+            end_ts: 200,
+        })
+        .await,
+        CustomError::InvalidSaleWindow,
+    );
+    assert_custom_error(
+        test.update_sale(Batch0SaleInstruction::SetSaleWindow {
+            start_ts: 200,
+            end_ts: 100,
+        })
+        .await,
+        CustomError::InvalidSaleWindow,
+    );
+
+    let mut test = SaleTest::new().await;
+    let shelf = test.shelf.insecure_clone();
+    let ix = instruction::init_sale(
+        &test.program_id,
+        &test.context.payer.pubkey(),
+        &test.till,
+        &shelf.pubkey(),
+        &test.mint,
+        PRICE,
+        200,
+        100,
+        0,
+    );
+    assert_custom_error(
+        send(&mut test.context, &[ix], &[&shelf]).await,
+        CustomError::InvalidSaleWindow,
+    );
+}
+
+#[tokio::test]
+async fn update_price_changes_what_buyers_pay() {
+    let mut test = SaleTest::with_sale().await;
+
+    let ix = instruction::update_price(
+        &test.program_id,
+        &test.context.payer.pubkey(),
+        &test.sale,
+        PRICE * 3,
+    );
+    send(&mut test.context, &[ix], &[]).await.unwrap();
+    assert_eq!(test.sale_data().await.current_price_per_token, PRICE * 3);
+
+    let till_before = test.lamports(test.till).await;
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.lamports(test.till).await - till_before, 1000 * PRICE * 3);
+}
+
+#[tokio::test]
+async fn update_price_requires_the_authority() {
+    let mut test = SaleTest::with_sale().await;
+
+    let buyer = test.buyer.insecure_clone();
+    let ix = instruction::update_price(&test.program_id, &buyer.pubkey(), &test.sale, PRICE * 3);
+    assert_custom_error(
+        send(&mut test.context, &[ix], &[&buyer]).await,
+        CustomError::WrongAuthority,
+    );
+}
+
+#[tokio::test]
+async fn paused_sales_refuse_buys_until_resumed() {
+    let mut test = SaleTest::with_sale().await;
+
+    test.update_sale(Batch0SaleInstruction::PauseSale {}).await.unwrap();
+    assert_eq!(test.sale_data().await.status, SaleStatus::Paused);
+    let ix = test.buy_package_ix(0).await;
+    assert_custom_error(test.buy(ix).await, CustomError::SalePaused);
+    let ix = test.buy_tokens_ix(500).await;
+    assert_custom_error(test.buy(ix).await, CustomError::SalePaused);
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 0);
+
+    //only the authority pulls the lever
+    let buyer = test.buyer.insecure_clone();
+    let ix = instruction::update_sale(
+        &test.program_id,
+        &buyer.pubkey(),
+        &test.sale,
+        Batch0SaleInstruction::ResumeSale {},
+    );
+    assert_custom_error(
+        send(&mut test.context, &[ix], &[&buyer]).await,
+        CustomError::WrongAuthority,
+    );
+
+    test.update_sale(Batch0SaleInstruction::ResumeSale {}).await.unwrap();
+    assert_eq!(test.sale_data().await.status, SaleStatus::Active);
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 1000);
+}
+
+#[tokio::test]
+async fn authority_handover_needs_the_proposed_key_to_accept() {
+    let mut test = SaleTest::with_sale().await;
+    let new_authority = Keypair::new();
+    let accept = instruction::accept_authority(&test.program_id, &new_authority.pubkey(), &test.sale);
+
+    assert_custom_error(
+        send(&mut test.context, std::slice::from_ref(&accept), &[&new_authority]).await,
+        CustomError::NoPendingAuthority,
+    );
+
+    test.update_sale(Batch0SaleInstruction::ProposeAuthority {
+        new_authority: new_authority.pubkey(),
+    })
+    .await
+    .unwrap();
+    assert_eq!(test.sale_data().await.pending_authority, new_authority.pubkey());
+
+    let buyer = test.buyer.insecure_clone();
+    let ix = instruction::accept_authority(&test.program_id, &buyer.pubkey(), &test.sale);
+    assert_custom_error(
+        send(&mut test.context, &[ix], &[&buyer]).await,
+        CustomError::WrongAuthority,
+    );
+
+    let mut unsigned = accept.clone();
+    unsigned.accounts[0].is_signer = false;
+    assert_custom_error(
+        send(&mut test.context, &[unsigned], &[]).await,
+        CustomError::MissingAuthoritySignature,
+    );
+
+    send(&mut test.context, &[accept], &[&new_authority]).await.unwrap();
+    let sale_data = test.sale_data().await;
+    assert_eq!(sale_data.authority, new_authority.pubkey());
+    assert_eq!(sale_data.pending_authority, Pubkey::default());
+
+    //the old authority is locked out, the new one is in
+    assert_custom_error(
+        test.update_sale(Batch0SaleInstruction::UpdatePrice {
+            new_price_per_token: PRICE * 2,
+        })
+        .await,
+        CustomError::WrongAuthority,
+    );
+    let ix = instruction::update_price(
+        &test.program_id,
+        &new_authority.pubkey(),
+        &test.sale,
+        PRICE * 2,
+    );
+    send(&mut test.context, &[ix], &[&new_authority]).await.unwrap();
+    assert_eq!(test.sale_data().await.current_price_per_token, PRICE * 2);
+}
+
+#[tokio::test]
+async fn buying_with_a_stale_price_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    let sale_data = test.sale_data().await;
+    let ix = instruction::buy_package(
+        &test.program_id,
+        &test.buy_accounts(),
+        &sale_data,
+        0,
+        u64::MAX,
+        PRICE + 1,
+    );
+    assert_custom_error(test.buy(ix).await, CustomError::PriceChanged);
+}
+
+async fn buy_with_payment_mint(token_program: Pubkey) {
+    let mut test = SaleTest::with_sale().await;
+    let buyer_payment_account = test.switch_to_payment_mint(&token_program, 3).await;
+
+    let sale_data = test.sale_data().await;
+    assert_eq!(sale_data.till_pubkey, test.till);
+    assert_eq!(sale_data.current_price_per_token, 3);
+
+    let mut accounts = test.buy_accounts();
+    accounts.payment = Some(PaymentAccounts {
+        buyer_payment_account,
+        payment_token_program: token_program,
+    });
+    let buyer_lamports = test.lamports(test.buyer.pubkey()).await;
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, 3000, 3);
+    test.buy(ix).await.unwrap();
+
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 1000);
+    assert_eq!(test.token_balance(test.till).await, 3000);
+    assert_eq!(test.token_balance(buyer_payment_account).await, PAYMENT_SUPPLY - 3000);
+    //only the buyer record's rent is paid in lamports
+    let (buyer_record, _) =
+        find_buyer_record_address(&test.sale, &test.buyer.pubkey(), &test.program_id);
+    assert_eq!(
+        buyer_lamports - test.lamports(test.buyer.pubkey()).await,
+        test.lamports(buyer_record).await
+    );
+    assert_eq!(test.sale_data().await.total_collected, 3000);
+}
+
+#[tokio::test]
+async fn buying_with_an_spl_token_payment_mint() {
+    buy_with_payment_mint(spl_token::id()).await;
+}
+
+#[tokio::test]
+async fn buying_with_a_token_2022_payment_mint() {
+    buy_with_payment_mint(spl_token_2022::id()).await;
+}
+
+#[tokio::test]
+async fn payment_mint_must_match_the_sale_and_the_till() {
+    let mut test = SaleTest::with_sale().await;
+    let buyer_payment_account = test.switch_to_payment_mint(&spl_token::id(), 3).await;
+
+    //a till of another mint cannot take payments
+    let other_mint = create_mint(&mut test.context, &spl_token::id()).await;
+    let ix = instruction::set_payment_mint(
+        &test.program_id,
+        &test.context.payer.pubkey(),
+        &test.sale,
+        &test.till,
+        Some(&other_mint),
+        3,
+    );
+    assert_custom_error(
+        send(&mut test.context, &[ix], &[]).await,
+        CustomError::InvalidPaymentMint,
+    );
+
+    //a buyer paying in another mint than the sale's
+    let mut sale_data = test.sale_data().await;
+    sale_data.payment_mint_pubkey = other_mint;
+    let mut accounts = test.buy_accounts();
+    accounts.payment = Some(PaymentAccounts {
+        buyer_payment_account,
+        payment_token_program: spl_token::id(),
+    });
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+    assert_custom_error(test.buy(ix).await, CustomError::InvalidPaymentMint);
+}
+
+#[tokio::test]
+async fn payment_token_program_must_own_the_payment_mint() {
+    let mut test = SaleTest::with_sale().await;
+    let buyer_payment_account = test.switch_to_payment_mint(&spl_token::id(), 3).await;
+    let sale_data = test.sale_data().await;
+
+    let mut accounts = test.buy_accounts();
+    accounts.payment = Some(PaymentAccounts {
+        buyer_payment_account,
+        payment_token_program: solana_sdk::system_program::id(),
+    });
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+    assert_custom_error(test.buy(ix).await, CustomError::IncorrectPaymentTokenProgram);
+
+    //a token program, but not the one owning the mint
+    accounts.payment = Some(PaymentAccounts {
+        buyer_payment_account,
+        payment_token_program: spl_token_2022::id(),
+    });
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+    assert_custom_error(test.buy(ix).await, CustomError::IncorrectAccountOwner);
+}
+
+#[tokio::test]
+async fn end_token_sale_returns_the_shelf_and_closes_the_sale() {
+    let mut test = SaleTest::with_sale().await;
+
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+
+    let sale_data = test.sale_data().await;
+    let ix = instruction::end_token_sale(
+        &test.program_id,
+        &test.context.payer.pubkey(),
+        &test.sale,
+        &sale_data,
+        &test.inventory_token_account,
+        &[],
+    );
+    send(&mut test.context, &[ix], &[]).await.unwrap();
+
+    assert_eq!(test.token_balance(test.inventory_token_account).await, SHELF_SUPPLY - 1000);
+    assert_eq!(test.token_balance(test.shelf.pubkey()).await, 0);
+    assert!(test.context.banks_client.get_account(test.sale).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn transfer_hook_runs_on_buys_and_when_the_sale_ends() {
+    let hook = TransferHook::new();
+    let mut test = SaleTest::with_mint_extensions(None, Some(&hook)).await;
+    test.init_sale().await.unwrap();
+
+    //Token-2022 cannot call the hook without its accounts
+    let ix = test.buy_package_ix(0).await;
+    assert!(test.buy(ix).await.is_err());
+
+    let sale_data = test.sale_data().await;
+    let mut accounts = test.buy_accounts();
+    accounts.extra_accounts = hook.extra_accounts(&test.mint);
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+    test.buy(ix).await.unwrap();
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 1000);
+    assert_eq!(hook_calls(&mut test, &hook).await, 1);
+
+    let ix = instruction::end_token_sale(
+        &test.program_id,
+        &test.context.payer.pubkey(),
+        &test.sale,
+        &sale_data,
+        &test.inventory_token_account,
+        &hook.extra_accounts(&test.mint),
+    );
+    send(&mut test.context, &[ix], &[]).await.unwrap();
+    assert_eq!(test.token_balance(test.inventory_token_account).await, SHELF_SUPPLY - 1000);
+    assert_eq!(hook_calls(&mut test, &hook).await, 2);
+}
+
+async fn hook_calls(test: &mut SaleTest, hook: &TransferHook) -> u64 {
+    let account = test.context.banks_client.get_account(hook.counter).await.unwrap().unwrap();
+    return u64::from_le_bytes(account.data[..8].try_into().unwrap());
+}
+
+#[tokio::test]
+async fn buying_with_the_wrong_till_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    let mut ix = test.buy_package_ix(0).await;
+    ix.accounts[2].pubkey = test.buyer.pubkey();
+    assert_custom_error(test.buy(ix).await, CustomError::InvalidTillAccount);
+}
+
+#[tokio::test]
+async fn buying_from_the_wrong_shelf_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    let mut ix = test.buy_package_ix(0).await;
+    ix.accounts[3].pubkey = test.inventory_token_account;
+    assert_custom_error(test.buy(ix).await, CustomError::InvalidShelfAccount);
+}
+
+#[tokio::test]
+async fn buying_without_the_buyer_signature_fails() {
+    let mut test = SaleTest::with_sale().await;
+
+    let mut ix = test.buy_package_ix(0).await;
+    ix.accounts[0].is_signer = false;
+    assert_custom_error(send(&mut test.context, &[ix], &[]).await, CustomError::AccountNotSigner);
+}
+
+#[tokio::test]
+async fn buying_creates_the_buyer_token_account_when_asked() {
+    let mut test = SaleTest::with_sale().await;
+    let buyer_ata = get_associated_token_address_with_program_id(
+        &test.buyer.pubkey(),
+        &test.mint,
+        &spl_token_2022::id(),
+    );
+    let tier = PackageTier::default_tiers()[0];
+
+    //the second purchase finds the account already there
+    for purchase in 1..=2 {
+        let sale_data = test.sale_data().await;
+        let accounts = BuyAccounts {
+            buyer: test.buyer.pubkey(),
+            sale: test.sale,
+            buyer_token_account: buyer_ata,
+            purchase_index: 0,
+            payment: None,
+            create_buyer_token_account: true,
+            recipient: None,
+            extra_accounts: vec![],
+        };
+        let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+        test.buy(ix).await.unwrap();
+
+        assert_eq!(
+            test.token_balance(buyer_ata).await,
+            purchase * (tier.sold_tokens + tier.bonus_tokens)
+        );
+    }
+}
+
+#[tokio::test]
+async fn gift_purchases_go_to_the_recipient_token_account() {
+    let mut test = SaleTest::with_sale().await;
+    let recipient = Pubkey::new_unique();
+    let recipient_ata =
+        get_associated_token_address_with_program_id(&recipient, &test.mint, &spl_token_2022::id());
+    let tier = PackageTier::default_tiers()[0];
+    let till_before = test.lamports(test.till).await;
+
+    let sale_data = test.sale_data().await;
+    let accounts = BuyAccounts {
+        buyer: test.buyer.pubkey(),
+        sale: test.sale,
+        buyer_token_account: recipient_ata,
+        purchase_index: 0,
+        payment: None,
+        create_buyer_token_account: true,
+        recipient: Some(recipient),
+        extra_accounts: vec![],
+    };
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+    let buyer = test.buyer.insecure_clone();
+    let blockhash = test.context.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test.context.payer.pubkey()),
+        &[&test.context.payer, &buyer],
+        blockhash,
+    );
+    let result = test
+        .context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    result.result.unwrap();
+
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    let receipt = PurchaseReceipt::unpack(&return_data.data).unwrap();
+    assert_eq!(receipt.buyer_pubkey, test.buyer.pubkey());
+    assert_eq!(receipt.recipient_pubkey, recipient);
+
+    assert_eq!(test.token_balance(recipient_ata).await, tier.sold_tokens + tier.bonus_tokens);
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 0);
+    assert_eq!(test.lamports(test.till).await - till_before, tier.sold_tokens * PRICE);
+}
+
+#[tokio::test]
+async fn gifting_to_a_non_associated_token_account_fails() {
+    let mut test = SaleTest::with_sale().await;
+    let sale_data = test.sale_data().await;
+    let accounts = BuyAccounts {
+        buyer: test.buyer.pubkey(),
+        sale: test.sale,
+        buyer_token_account: test.buyer_token_account,
+        purchase_index: 0,
+        payment: None,
+        create_buyer_token_account: false,
+        recipient: Some(Pubkey::new_unique()),
+        extra_accounts: vec![],
+    };
+    let ix = instruction::buy_package(&test.program_id, &accounts, &sale_data, 0, u64::MAX, 0);
+
+    assert_custom_error(test.buy(ix).await, CustomError::InvalidRecipientTokenAccount);
+}
+
+#[tokio::test]
+async fn transfer_fee_is_grossed_up_by_default() {
+    let mut test = SaleTest::with_transfer_fee(Some(100)).await;
+    test.init_sale().await.unwrap();
+    assert_eq!(test.sale_data().await.transfer_fee_mode, TransferFeeMode::GrossUp);
+
+    let till_before = test.lamports(test.till).await;
+    let ix = test.buy_package_ix(0).await;
+    test.buy(ix).await.unwrap();
+
+    //the shelf sends 1011 so the 11-token fee leaves the buyer the full package
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 1000);
+    assert_eq!(test.token_balance(test.shelf.pubkey()).await, SHELF_SUPPLY - 1011);
+    assert_eq!(test.lamports(test.till).await - till_before, 1000 * PRICE);
+}
+
+#[tokio::test]
+async fn transfer_fee_charged_to_the_buyer_is_billed_at_the_token_price() {
+    let mut test = SaleTest::with_transfer_fee(Some(100)).await;
+    test.init_sale().await.unwrap();
+    test.update_sale(Batch0SaleInstruction::UpdatePrice {
+        new_price_per_token: 3,
+    })
+    .await
+    .unwrap();
+    test.update_sale(Batch0SaleInstruction::SetTransferFeeMode {
+        transfer_fee_mode: TransferFeeMode::ChargeBuyer,
+    })
+    .await
+    .unwrap();
+
+    //the fee tokens count against the buyer's maximum
+    let sale_data = test.sale_data().await;
+    let ix = instruction::buy_package(
+        &test.program_id,
+        &test.buy_accounts(),
+        &sale_data,
+        0,
+        1000 * 3,
+        3,
+    );
+    assert_custom_error(test.buy(ix).await, CustomError::PriceChanged);
+
+    let till_before = test.lamports(test.till).await;
+    let ix = instruction::buy_package(
+        &test.program_id,
+        &test.buy_accounts(),
+        &sale_data,
+        0,
+        1011 * 3,
+        3,
+    );
+    test.buy(ix).await.unwrap();
+
+    assert_eq!(test.token_balance(test.buyer_token_account).await, 1000);
+    assert_eq!(test.token_balance(test.shelf.pubkey()).await, SHELF_SUPPLY - 1011);
+    assert_eq!(test.lamports(test.till).await - till_before, 1011 * 3);
+    assert_eq!(test.sale_data().await.total_collected, 1011 * 3);
+}